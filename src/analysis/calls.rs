@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+use nu_protocol::ir::Instruction;
+
+use super::{registers, Cfg};
+
+/// Whether an instruction sets up the arguments or redirection for the next call
+pub fn is_call_setup(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::PushPositional { .. }
+            | Instruction::AppendRest { .. }
+            | Instruction::PushFlag { .. }
+            | Instruction::PushShortFlag { .. }
+            | Instruction::PushNamed { .. }
+            | Instruction::PushShortNamed { .. }
+            | Instruction::PushParserInfo { .. }
+            | Instruction::RedirectOut { .. }
+            | Instruction::RedirectErr { .. }
+            | Instruction::OpenFile { .. }
+    )
+}
+
+/// Find the runs of instructions leading up to each call that only set up its arguments, including
+/// the instructions that load the argument values. Each range ends with the call.
+pub fn call_setup_ranges(instructions: &[Instruction]) -> Vec<Range<usize>> {
+    let cfg = Cfg::new(instructions);
+    let mut ranges = vec![];
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        if !matches!(instruction, Instruction::Call { .. }) {
+            continue;
+        }
+        let mut start = index;
+        let mut used = vec![];
+        let mut any_setup = false;
+        while let Some(prev) = start.checked_sub(1) {
+            if cfg.straight_line_next(prev) != Some(start) {
+                break;
+            }
+            let prev_instruction = &instructions[prev];
+            let defs = registers::defs(prev_instruction);
+            if is_call_setup(prev_instruction) {
+                any_setup = true;
+            } else if matches!(prev_instruction, Instruction::Call { .. })
+                || defs.is_empty()
+                || !defs.iter().all(|reg_id| used.contains(reg_id))
+            {
+                // Only include instructions that load values for the arguments
+                break;
+            }
+            used.extend(registers::uses(prev_instruction));
+            start = prev;
+        }
+        if any_setup {
+            ranges.push(start..index + 1);
+        }
+    }
+
+    ranges
+}
@@ -0,0 +1,305 @@
+use std::{collections::VecDeque, ops::Range};
+
+use nu_protocol::ir::Instruction;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Execution continues with the next instruction
+    Next,
+    /// A jump, or a conditional branch that was taken
+    Branch,
+    /// An error handler that was pushed by `OnError` or `OnErrorInto` being invoked
+    Error,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+/// Get the possible successors of an instruction, including any targets that might be out of range.
+///
+/// An error handler is treated as a successor of the instruction that pushes it.
+pub fn successors(instruction: &Instruction, index: usize) -> Vec<Edge> {
+    let next = Edge {
+        target: index + 1,
+        kind: EdgeKind::Next,
+    };
+    match instruction {
+        Instruction::Unreachable | Instruction::Return { .. } | Instruction::ReturnEarly { .. } => {
+            vec![]
+        }
+        Instruction::Jump { index } => vec![Edge {
+            target: *index,
+            kind: EdgeKind::Branch,
+        }],
+        Instruction::OnError { index } | Instruction::OnErrorInto { index, .. } => vec![
+            next,
+            Edge {
+                target: *index,
+                kind: EdgeKind::Error,
+            },
+        ],
+        _ => match instruction.branch_target() {
+            Some(target) => vec![
+                next,
+                Edge {
+                    target,
+                    kind: EdgeKind::Branch,
+                },
+            ],
+            None => vec![next],
+        },
+    }
+}
+
+/// The control flow graph of a block, with only the edges that are in range
+pub struct Cfg {
+    pub successors: Vec<Vec<Edge>>,
+    pub predecessors: Vec<Vec<usize>>,
+}
+
+impl Cfg {
+    pub fn new(instructions: &[Instruction]) -> Cfg {
+        let len = instructions.len();
+        let successors: Vec<Vec<Edge>> = instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| {
+                successors(instruction, index)
+                    .into_iter()
+                    .filter(|edge| edge.target < len)
+                    .collect()
+            })
+            .collect();
+
+        let mut predecessors = vec![vec![]; len];
+        for (index, edges) in successors.iter().enumerate() {
+            for edge in edges {
+                predecessors[edge.target].push(index);
+            }
+        }
+
+        Cfg {
+            successors,
+            predecessors,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.successors.len()
+    }
+
+    /// Get the instruction after `index` if control always flows straight from one to the other,
+    /// which means they're in the same basic block
+    pub fn straight_line_next(&self, index: usize) -> Option<usize> {
+        match self.successors[index][..] {
+            [Edge {
+                target,
+                kind: EdgeKind::Next,
+            }] if self.predecessors[target] == [index] => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Get the range of the basic block that contains an instruction
+    pub fn basic_block(&self, index: usize) -> Range<usize> {
+        let mut start = index;
+        while let Some(prev) = start.checked_sub(1) {
+            if self.straight_line_next(prev) != Some(start) {
+                break;
+            }
+            start = prev;
+        }
+        let mut end = index;
+        while let Some(next) = self.straight_line_next(end) {
+            end = next;
+        }
+        start..end + 1
+    }
+
+    /// Find which instructions can be reached from the first instruction
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.len()];
+        let mut queue = VecDeque::from_iter((self.len() > 0).then_some(0));
+        while let Some(index) = queue.pop_front() {
+            if !std::mem::replace(&mut reachable[index], true) {
+                queue.extend(self.successors[index].iter().map(|edge| edge.target));
+            }
+        }
+        reachable
+    }
+
+    /// Run a forward dataflow analysis to a fixed point, returning the state on entry to each
+    /// instruction, or `None` if it's unreachable.
+    ///
+    /// `transfer` computes the state along an edge out of an instruction given the state on entry
+    /// to it, and `join` merges states where paths meet at an instruction.
+    pub fn forward<S: Clone + PartialEq>(
+        &self,
+        entry: S,
+        mut transfer: impl FnMut(usize, &S, Edge) -> S,
+        mut join: impl FnMut(usize, &S, &S) -> S,
+    ) -> Vec<Option<S>> {
+        let mut states: Vec<Option<S>> = vec![None; self.len()];
+        if self.len() == 0 {
+            return states;
+        }
+        states[0] = Some(entry);
+
+        let mut queue = VecDeque::from([0]);
+        let mut queued = vec![false; self.len()];
+        queued[0] = true;
+
+        while let Some(index) = queue.pop_front() {
+            queued[index] = false;
+            let Some(state) = states[index].clone() else {
+                continue;
+            };
+            for &edge in &self.successors[index] {
+                let out = transfer(index, &state, edge);
+                let merged = match &states[edge.target] {
+                    Some(existing) => join(edge.target, existing, &out),
+                    None => out,
+                };
+                if states[edge.target].as_ref() != Some(&merged) {
+                    states[edge.target] = Some(merged);
+                    if !std::mem::replace(&mut queued[edge.target], true) {
+                        queue.push_back(edge.target);
+                    }
+                }
+            }
+        }
+
+        states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{ir::Literal, RegId};
+
+    use super::*;
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    fn next(target: usize) -> Edge {
+        Edge {
+            target,
+            kind: EdgeKind::Next,
+        }
+    }
+
+    fn branch(target: usize) -> Edge {
+        Edge {
+            target,
+            kind: EdgeKind::Branch,
+        }
+    }
+
+    /// `if $in { 1 } else { 2 }`
+    fn if_else() -> Vec<Instruction> {
+        vec![
+            Instruction::BranchIf {
+                cond: reg(0),
+                index: 3,
+            },
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(2),
+            },
+            Instruction::Jump { index: 4 },
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(1),
+            },
+            Instruction::Return { src: reg(1) },
+        ]
+    }
+
+    #[test]
+    fn finds_successors() {
+        let cfg = Cfg::new(&if_else());
+        assert_eq!(cfg.successors[0], vec![next(1), branch(3)]);
+        assert_eq!(cfg.successors[2], vec![branch(4)]);
+        assert_eq!(cfg.successors[4], vec![]);
+        assert_eq!(cfg.predecessors[4], vec![2, 3]);
+    }
+
+    #[test]
+    fn treats_error_handler_as_successor() {
+        let instruction = Instruction::OnErrorInto {
+            index: 7,
+            dst: reg(1),
+        };
+        assert_eq!(
+            successors(&instruction, 2),
+            vec![
+                next(3),
+                Edge {
+                    target: 7,
+                    kind: EdgeKind::Error
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_edges_out_of_range() {
+        let instructions = vec![Instruction::BranchIf {
+            cond: reg(0),
+            index: 9,
+        }];
+        assert_eq!(successors(&instructions[0], 0), vec![next(1), branch(9)]);
+        assert_eq!(Cfg::new(&instructions).successors[0], vec![]);
+    }
+
+    #[test]
+    fn finds_basic_blocks() {
+        let cfg = Cfg::new(&if_else());
+        assert_eq!(cfg.straight_line_next(0), None);
+        assert_eq!(cfg.straight_line_next(1), Some(2));
+        assert_eq!(cfg.basic_block(2), 1..3);
+        assert_eq!(cfg.basic_block(4), 4..5);
+    }
+
+    #[test]
+    fn finds_reachable_instructions() {
+        let mut instructions = if_else();
+        instructions.insert(5, Instruction::Return { src: reg(0) });
+        let cfg = Cfg::new(&instructions);
+        assert_eq!(cfg.reachable(), vec![true, true, true, true, true, false]);
+    }
+
+    #[test]
+    fn joins_states_where_paths_meet() {
+        let instructions = if_else();
+        let cfg = Cfg::new(&instructions);
+        // Collect the instructions that each path went through
+        let states = cfg.forward(
+            vec![],
+            |index, path: &Vec<usize>, _| {
+                let mut path = path.clone();
+                path.push(index);
+                path
+            },
+            |_, a, b| {
+                let mut joined = a.clone();
+                joined.extend(b.iter().filter(|index| !a.contains(index)));
+                joined.sort();
+                joined
+            },
+        );
+        assert_eq!(states[1], Some(vec![0]));
+        assert_eq!(states[4], Some(vec![0, 1, 2, 3]));
+    }
+
+    #[test]
+    fn forward_handles_empty_block() {
+        let cfg = Cfg::new(&[]);
+        assert_eq!(cfg.forward((), |_, _, _| (), |_, _, _| ()), vec![]);
+    }
+}
@@ -0,0 +1,358 @@
+use nu_protocol::{
+    ast::{Bits, Boolean, Comparison, Math, Operator},
+    ir::{Instruction, IrBlock, Literal},
+    Config, ShellError, Span, Value, VarId,
+};
+
+use super::{data_slice, data_str, registers, Cfg, Edge, EdgeKind};
+
+/// The registers and variables that hold a known value at a point in the block
+#[derive(Debug, Clone, PartialEq)]
+struct ConstState {
+    registers: Vec<Option<Value>>,
+    /// Variables stored in this block, and their value if it's always the same
+    variables: Vec<(VarId, Option<Value>)>,
+}
+
+/// Whether an instruction computes its result from other registers, so that it could be done at
+/// compile time if they're all constant
+pub fn is_foldable(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::BinaryOp { .. } | Instruction::Not { .. } | Instruction::StringAppend { .. }
+    )
+}
+
+/// Find the value written by each instruction if it's always the same, by propagating constants
+/// from literals through moves, variables, operators and string interpolation
+pub fn constants(ir_block: &IrBlock) -> Vec<Option<Value>> {
+    let instructions = &ir_block.instructions;
+    let cfg = Cfg::new(instructions);
+    let entry_state = ConstState {
+        registers: vec![None; ir_block.register_count as usize],
+        variables: vec![],
+    };
+    let transfer = |index: usize, state: &ConstState, edge: Edge| {
+        let mut out = state.clone();
+        if let Some(reg_id) = registers::def(&instructions[index], edge.kind) {
+            let value = if edge.kind == EdgeKind::Error {
+                None
+            } else {
+                output(ir_block, index, state)
+            };
+            if let Some(register) = out.registers.get_mut(reg_id.get() as usize) {
+                *register = value;
+            }
+        }
+        if let Instruction::StoreVariable { var_id, src } = instructions[index] {
+            let value = state.registers.get(src.get() as usize).cloned().flatten();
+            match out.variables.iter_mut().find(|(id, _)| *id == var_id) {
+                Some((_, existing)) => *existing = value,
+                None => out.variables.push((var_id, value)),
+            }
+        }
+        out
+    };
+
+    let entry = cfg.forward(entry_state, transfer, |_, a, b| {
+        // A variable that wasn't stored to on one of the paths still has whatever it had before
+        // the block, so it's only known if both paths stored the same value
+        let variables = a
+            .variables
+            .iter()
+            .filter_map(|(var_id, value)| {
+                let (_, other) = b.variables.iter().find(|(id, _)| id == var_id)?;
+                Some((*var_id, value.clone().filter(|_| same(value, other))))
+            })
+            .collect();
+        ConstState {
+            registers: a
+                .registers
+                .iter()
+                .zip(&b.registers)
+                .map(|(a, b)| if same(a, b) { a.clone() } else { None })
+                .collect(),
+            variables,
+        }
+    });
+
+    entry
+        .iter()
+        .enumerate()
+        .map(|(index, state)| output(ir_block, index, state.as_ref()?))
+        .collect()
+}
+
+/// Whether two constants are the same, including their type, since values compare `1` and `1.0` as
+/// equal
+fn same(a: &Option<Value>, b: &Option<Value>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b && a.get_type() == b.get_type(),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Compute the value that an instruction writes given the constants on entry to it. `NaN` isn't
+/// treated as a constant, because it isn't equal to itself.
+fn output(ir_block: &IrBlock, index: usize, state: &ConstState) -> Option<Value> {
+    compute(ir_block, index, state)
+        .filter(|value| !matches!(value, Value::Float { val, .. } if val.is_nan()))
+}
+
+fn compute(ir_block: &IrBlock, index: usize, state: &ConstState) -> Option<Value> {
+    let span = Span::unknown();
+    let register = |reg_id: nu_protocol::RegId| state.registers.get(reg_id.get() as usize)?.clone();
+    match &ir_block.instructions[index] {
+        Instruction::LoadLiteral { lit, .. } => literal_value(ir_block, lit),
+        Instruction::LoadValue { val, .. } => Some((**val).clone()),
+        Instruction::Move { src, .. } | Instruction::Clone { src, .. } => register(*src),
+        Instruction::Span { src_dst } | Instruction::Collect { src_dst } => register(*src_dst),
+        Instruction::LoadVariable { var_id, .. } => state
+            .variables
+            .iter()
+            .find(|(id, _)| id == var_id)
+            .and_then(|(_, value)| value.clone()),
+        Instruction::Not { src_dst } => {
+            Some(Value::bool(!register(*src_dst)?.as_bool().ok()?, span))
+        }
+        Instruction::BinaryOp { lhs_dst, op, rhs } => {
+            binary_op(op, &register(*lhs_dst)?, &register(*rhs)?, span)?.ok()
+        }
+        Instruction::StringAppend { src_dst, val } => {
+            let mut string = register(*src_dst)?.into_string().ok()?;
+            // Other types are formatted according to the config, which could be different
+            match register(*val)? {
+                Value::String { val, .. } => string.push_str(&val),
+                val @ (Value::Int { .. } | Value::Bool { .. } | Value::Nothing { .. }) => {
+                    string.push_str(&val.to_expanded_string(", ", &Config::default()))
+                }
+                _ => return None,
+            }
+            Some(Value::string(string, span))
+        }
+        _ => None,
+    }
+}
+
+/// Format a constant for showing next to an instruction, with strings quoted and `null` spelled
+/// out
+pub fn display_constant(value: &Value) -> String {
+    match value {
+        Value::String { val, .. } => format!("{val:?}"),
+        Value::Nothing { .. } => "null".into(),
+        _ => value.to_expanded_string(", ", &Config::default()),
+    }
+}
+
+/// Get the value of a literal that doesn't depend on the environment or other registers
+pub fn literal_value(ir_block: &IrBlock, lit: &Literal) -> Option<Value> {
+    let span = Span::unknown();
+    Some(match lit {
+        Literal::Bool(val) => Value::bool(*val, span),
+        Literal::Int(val) => Value::int(*val, span),
+        Literal::Float(val) => Value::float(*val, span),
+        Literal::Filesize(val) => Value::filesize(*val, span),
+        Literal::Duration(val) => Value::duration(*val, span),
+        Literal::Binary(slice) => Value::binary(data_slice(ir_block, *slice)?, span),
+        Literal::String(slice) | Literal::RawString(slice) => {
+            Value::string(data_str(ir_block, *slice)?, span)
+        }
+        Literal::CellPath(path) => Value::cell_path((**path).clone(), span),
+        Literal::Date(date) => Value::date(**date, span),
+        Literal::Nothing => Value::nothing(span),
+        _ => return None,
+    })
+}
+
+/// Evaluate a binary operator like the engine does. Returns `None` for operators that need the
+/// engine state, like regex matches.
+pub fn binary_op(
+    op: &Operator,
+    lhs: &Value,
+    rhs: &Value,
+    span: Span,
+) -> Option<Result<Value, ShellError>> {
+    Some(match op {
+        Operator::Comparison(comparison) => match comparison {
+            Comparison::Equal => lhs.eq(span, rhs, span),
+            Comparison::NotEqual => lhs.ne(span, rhs, span),
+            Comparison::LessThan => lhs.lt(span, rhs, span),
+            Comparison::GreaterThan => lhs.gt(span, rhs, span),
+            Comparison::LessThanOrEqual => lhs.lte(span, rhs, span),
+            Comparison::GreaterThanOrEqual => lhs.gte(span, rhs, span),
+            Comparison::RegexMatch | Comparison::NotRegexMatch => return None,
+            Comparison::In => lhs.r#in(span, rhs, span),
+            Comparison::NotIn => lhs.not_in(span, rhs, span),
+            Comparison::StartsWith => lhs.starts_with(span, rhs, span),
+            Comparison::EndsWith => lhs.ends_with(span, rhs, span),
+        },
+        Operator::Math(math) => match math {
+            Math::Plus => lhs.add(span, rhs, span),
+            Math::Append => lhs.append(span, rhs, span),
+            Math::Minus => lhs.sub(span, rhs, span),
+            Math::Multiply => lhs.mul(span, rhs, span),
+            Math::Divide => lhs.div(span, rhs, span),
+            Math::Modulo => lhs.modulo(span, rhs, span),
+            Math::FloorDivision => lhs.floor_div(span, rhs, span),
+            Math::Pow => lhs.pow(span, rhs, span),
+        },
+        Operator::Boolean(boolean) => match boolean {
+            Boolean::And => lhs.and(span, rhs, span),
+            Boolean::Or => lhs.or(span, rhs, span),
+            Boolean::Xor => lhs.xor(span, rhs, span),
+        },
+        Operator::Bits(bits) => match bits {
+            Bits::BitOr => lhs.bit_or(span, rhs, span),
+            Bits::BitXor => lhs.bit_xor(span, rhs, span),
+            Bits::BitAnd => lhs.bit_and(span, rhs, span),
+            Bits::ShiftLeft => lhs.bit_shl(span, rhs, span),
+            Bits::ShiftRight => lhs.bit_shr(span, rhs, span),
+        },
+        Operator::Assignment(_) => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{
+        ir::{DataSlice, Instruction, Literal},
+        RegId,
+    };
+
+    use super::*;
+    use crate::analysis::test_util::{ir_block, ir_block_with_data};
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    fn int(val: i64) -> Value {
+        Value::int(val, Span::unknown())
+    }
+
+    /// `if $in { $x = then } else { $x = otherwise }; $x`, not storing on a branch if its value
+    /// is `None`. The load of `$x` is the last instruction before the return.
+    fn store_on_branches(then: Option<i64>, otherwise: Option<i64>) -> Vec<Instruction> {
+        let var_id = VarId::new(100);
+        let store = |val: Option<i64>| match val {
+            Some(val) => vec![
+                Instruction::LoadLiteral {
+                    dst: reg(1),
+                    lit: Literal::Int(val),
+                },
+                Instruction::StoreVariable {
+                    var_id,
+                    src: reg(1),
+                },
+            ],
+            // The same length as a store, so that the path that reaches the load first depends
+            // on which branch stores
+            None => vec![
+                Instruction::LoadLiteral {
+                    dst: reg(1),
+                    lit: Literal::Nothing,
+                },
+                Instruction::Drop { src: reg(1) },
+            ],
+        };
+        let otherwise = store(otherwise);
+        let then = store(then);
+        let mut instructions = vec![Instruction::BranchIf {
+            cond: reg(0),
+            index: otherwise.len() + 2,
+        }];
+        instructions.extend(otherwise);
+        instructions.push(Instruction::Jump {
+            index: instructions.len() + then.len() + 1,
+        });
+        instructions.extend(then);
+        instructions.push(Instruction::LoadVariable {
+            dst: reg(2),
+            var_id,
+        });
+        instructions.push(Instruction::Return { src: reg(2) });
+        instructions
+    }
+
+    fn loaded_constant(instructions: Vec<Instruction>) -> Option<Value> {
+        let load = instructions.len() - 2;
+        constants(&ir_block(instructions))[load].clone()
+    }
+
+    #[test]
+    fn folds_straight_line_code() {
+        let ir_block = ir_block(vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(2),
+            },
+            Instruction::LoadLiteral {
+                dst: reg(2),
+                lit: Literal::Int(3),
+            },
+            Instruction::BinaryOp {
+                lhs_dst: reg(1),
+                op: Operator::Math(Math::Multiply),
+                rhs: reg(2),
+            },
+            Instruction::Return { src: reg(1) },
+        ]);
+        assert_eq!(constants(&ir_block)[2], Some(int(6)));
+    }
+
+    #[test]
+    fn keeps_variable_stored_with_same_value_on_both_paths() {
+        assert_eq!(
+            loaded_constant(store_on_branches(Some(1), Some(1))),
+            Some(int(1))
+        );
+    }
+
+    #[test]
+    fn forgets_variable_stored_with_different_values() {
+        assert_eq!(loaded_constant(store_on_branches(Some(1), Some(2))), None);
+    }
+
+    #[test]
+    fn forgets_variable_stored_on_one_path() {
+        assert_eq!(loaded_constant(store_on_branches(Some(1), None)), None);
+        assert_eq!(loaded_constant(store_on_branches(None, Some(1))), None);
+    }
+
+    #[test]
+    fn keeps_int_and_float_apart() {
+        assert!(!same(
+            &Some(int(1)),
+            &Some(Value::float(1.0, Span::unknown()))
+        ));
+    }
+
+    #[test]
+    fn ignores_literal_outside_of_data() {
+        let ir_block = ir_block_with_data(
+            vec![
+                Instruction::LoadLiteral {
+                    dst: reg(1),
+                    lit: Literal::String(DataSlice { start: 1, len: 8 }),
+                },
+                Instruction::Return { src: reg(1) },
+            ],
+            b"abc",
+        );
+        assert_eq!(constants(&ir_block)[0], None);
+    }
+
+    #[test]
+    fn ignores_register_out_of_range() {
+        let mut ir_block = ir_block(vec![
+            Instruction::Move {
+                dst: reg(1),
+                src: reg(5),
+            },
+            Instruction::Return { src: reg(1) },
+        ]);
+        ir_block.register_count = 2;
+        assert_eq!(constants(&ir_block)[0], None);
+    }
+}
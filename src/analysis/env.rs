@@ -0,0 +1,150 @@
+use std::fmt;
+
+use nu_protocol::{
+    ir::{Instruction, IrBlock},
+    ENV_VARIABLE_ID,
+};
+
+use super::data_str;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvAccessKind {
+    /// `$env.NAME`, which fails if it isn't set
+    Read,
+    /// `$env.NAME?`, which is `null` if it isn't set
+    ReadOptional,
+    /// `$env.NAME = ...`
+    Write,
+    /// `$env` as a whole, which could be used to read any variable
+    ReadAll,
+}
+
+impl fmt::Display for EnvAccessKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvAccessKind::Read => f.write_str("read"),
+            EnvAccessKind::ReadOptional => f.write_str("read-optional"),
+            EnvAccessKind::Write => f.write_str("write"),
+            EnvAccessKind::ReadAll => f.write_str("read-all"),
+        }
+    }
+}
+
+/// An instruction that reads or writes an environment variable
+#[derive(Debug, Clone)]
+pub struct EnvAccess {
+    pub index: usize,
+    /// The name of the variable, or `$env` for the whole environment
+    pub name: String,
+    pub kind: EnvAccessKind,
+}
+
+/// Find every instruction that uses the environment, with the names of the variables decoded from
+/// the block's data
+pub fn env_accesses(ir_block: &IrBlock) -> Vec<EnvAccess> {
+    let name = |key| data_str(ir_block, key).unwrap_or_default().to_owned();
+    ir_block
+        .instructions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, instruction)| {
+            let (name, kind) = match instruction {
+                Instruction::LoadEnv { key, .. } => (name(*key), EnvAccessKind::Read),
+                Instruction::LoadEnvOpt { key, .. } => (name(*key), EnvAccessKind::ReadOptional),
+                Instruction::StoreEnv { key, .. } => (name(*key), EnvAccessKind::Write),
+                Instruction::LoadVariable { var_id, .. } if *var_id == ENV_VARIABLE_ID => {
+                    ("$env".into(), EnvAccessKind::ReadAll)
+                }
+                _ => return None,
+            };
+            Some(EnvAccess { index, name, kind })
+        })
+        .collect()
+}
+
+/// Group the accesses to each variable, in the order they're first used
+pub fn env_usage(accesses: &[EnvAccess]) -> Vec<(&str, Vec<&EnvAccess>)> {
+    let mut usage: Vec<(&str, Vec<&EnvAccess>)> = vec![];
+    for access in accesses {
+        match usage.iter_mut().find(|(name, _)| *name == access.name) {
+            Some((_, uses)) => uses.push(access),
+            None => usage.push((&access.name, vec![access])),
+        }
+    }
+    usage
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{ir::DataSlice, RegId, VarId};
+
+    use super::*;
+    use crate::analysis::test_util::ir_block_with_data;
+
+    fn key(start: u32, len: u32) -> DataSlice {
+        DataSlice { start, len }
+    }
+
+    #[test]
+    fn finds_accesses() {
+        let reg = RegId::new(1);
+        let ir_block = ir_block_with_data(
+            vec![
+                Instruction::LoadEnv {
+                    dst: reg,
+                    key: key(0, 4),
+                },
+                Instruction::LoadEnvOpt {
+                    dst: reg,
+                    key: key(4, 4),
+                },
+                Instruction::StoreEnv {
+                    key: key(0, 4),
+                    src: reg,
+                },
+                Instruction::LoadVariable {
+                    dst: reg,
+                    var_id: ENV_VARIABLE_ID,
+                },
+                Instruction::LoadVariable {
+                    dst: reg,
+                    var_id: VarId::new(100),
+                },
+                Instruction::LoadEnv {
+                    dst: reg,
+                    key: key(6, 4),
+                },
+            ],
+            b"PATHHOME",
+        );
+        let accesses: Vec<_> = env_accesses(&ir_block)
+            .into_iter()
+            .map(|access| (access.index, access.name, access.kind))
+            .collect();
+        assert_eq!(
+            accesses,
+            vec![
+                (0, "PATH".into(), EnvAccessKind::Read),
+                (1, "HOME".into(), EnvAccessKind::ReadOptional),
+                (2, "PATH".into(), EnvAccessKind::Write),
+                (3, "$env".into(), EnvAccessKind::ReadAll),
+                // The key is outside of the data
+                (5, "".into(), EnvAccessKind::Read),
+            ]
+        );
+
+        let usage: Vec<_> = env_usage(&env_accesses(&ir_block))
+            .into_iter()
+            .map(|(name, uses)| (name.to_owned(), uses.len()))
+            .collect();
+        assert_eq!(
+            usage,
+            vec![
+                ("PATH".into(), 2),
+                ("HOME".into(), 1),
+                ("$env".into(), 1),
+                ("".into(), 1),
+            ]
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use nu_protocol::ir::Instruction;
+
+use super::{Cfg, EdgeKind};
+
+/// An error handler pushed by `OnError` or `OnErrorInto`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorHandler {
+    /// The instruction that pushed the handler
+    pub pushed_at: usize,
+    /// The instruction that the handler jumps to when an error occurs
+    pub target: usize,
+}
+
+/// Get the stack of error handlers after control flow leaves an instruction along an edge of the
+/// given kind
+pub fn handlers_after(
+    instruction: &Instruction,
+    index: usize,
+    handlers: &[ErrorHandler],
+    kind: EdgeKind,
+) -> Vec<ErrorHandler> {
+    let mut handlers = handlers.to_vec();
+    match (instruction, kind) {
+        // The handler is popped when it's invoked
+        (_, EdgeKind::Error) => (),
+        (
+            Instruction::OnError { index: target } | Instruction::OnErrorInto { index: target, .. },
+            _,
+        ) => {
+            handlers.push(ErrorHandler {
+                pushed_at: index,
+                target: *target,
+            });
+        }
+        (Instruction::PopErrorHandler, _) => {
+            handlers.pop();
+        }
+        _ => (),
+    }
+    handlers
+}
+
+/// Find the stack of error handlers on entry to each instruction, innermost last. Where paths
+/// disagree (which `ir verify` reports), the first one found is kept.
+pub fn error_handlers(instructions: &[Instruction]) -> Vec<Vec<ErrorHandler>> {
+    Cfg::new(instructions)
+        .forward(
+            vec![],
+            |index, handlers, edge| {
+                handlers_after(&instructions[index], index, handlers, edge.kind)
+            },
+            |_, existing, _| existing.clone(),
+        )
+        .into_iter()
+        .map(Option::unwrap_or_default)
+        .collect()
+}
@@ -0,0 +1,222 @@
+use nu_protocol::ir::{Instruction, IrBlock};
+
+use super::{registers, Cfg, Diagnostic, Severity};
+
+/// Look for instruction patterns that do unnecessary work. These often point to script constructs
+/// that could be written differently to run faster.
+pub fn lint(ir_block: &IrBlock) -> Vec<Diagnostic> {
+    let instructions = &ir_block.instructions;
+    let cfg = Cfg::new(instructions);
+
+    // Dropping a value doesn't count as using it
+    let live_out = registers::live_out(
+        instructions,
+        &cfg,
+        |instruction| match instruction {
+            Instruction::Drop { .. } => vec![],
+            _ => registers::uses(instruction),
+        },
+        ir_block.register_count,
+    );
+    let is_live_after = |index: usize, reg_id: nu_protocol::RegId| {
+        live_out[index]
+            .get(reg_id.get() as usize)
+            .copied()
+            .unwrap_or(true)
+    };
+
+    // Find the next instruction in the same basic block that reads or writes `reg_id`
+    let next_access = |index: usize, reg_id| {
+        let mut next = cfg.straight_line_next(index);
+        while let Some(next_index) = next {
+            let instruction = &instructions[next_index];
+            if registers::uses(instruction).contains(&reg_id)
+                || registers::defs(instruction).contains(&reg_id)
+            {
+                return Some(next_index);
+            }
+            next = cfg.straight_line_next(next_index);
+        }
+        None
+    };
+
+    let mut diagnostics = vec![];
+    let mut warn = |index: usize, message: String| {
+        diagnostics.push(Diagnostic {
+            index: Some(index),
+            severity: Severity::Warning,
+            message,
+        })
+    };
+
+    for (index, instruction) in instructions.iter().enumerate() {
+        match *instruction {
+            Instruction::Clone { dst, src } => {
+                if !is_live_after(index, dst) {
+                    warn(index, format!("the clone in {dst} is never used"));
+                } else if !is_live_after(index, src) {
+                    warn(
+                        index,
+                        format!("{src} is never used again, so this could be a move"),
+                    );
+                }
+            }
+            Instruction::Move { dst, src } => {
+                if dst == src {
+                    warn(index, "moves a register to itself".into());
+                } else if let Some(next_index) = next_access(index, dst) {
+                    if let Instruction::Move {
+                        dst: next_dst,
+                        src: next_src,
+                    } = instructions[next_index]
+                    {
+                        if next_src == dst && !is_live_after(next_index, dst) {
+                            warn(
+                                index,
+                                format!(
+                                    "{src} → {dst} → {next_dst} (at {next_index}) could be a \
+                                    single move"
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+            Instruction::Collect { src_dst } => {
+                if let Some(next_index) = cfg.straight_line_next(index) {
+                    if matches!(
+                        instructions[next_index],
+                        Instruction::Collect { src_dst: next } if next == src_dst
+                    ) {
+                        warn(index, format!("{src_dst} is collected again immediately"));
+                    }
+                }
+            }
+            Instruction::LoadLiteral { dst, .. } | Instruction::LoadValue { dst, .. } => {
+                if let Some(next_index) = next_access(index, dst) {
+                    if matches!(instructions[next_index], Instruction::Drop { src } if src == dst) {
+                        warn(
+                            index,
+                            format!(
+                                "literal in {dst} is dropped at {next_index} without being used"
+                            ),
+                        );
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{ir::Literal, RegId, VarId};
+
+    use super::*;
+    use crate::analysis::test_util::ir_block;
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    fn messages(instructions: Vec<Instruction>) -> Vec<(Option<usize>, String)> {
+        lint(&ir_block(instructions))
+            .into_iter()
+            .map(|diagnostic| (diagnostic.index, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn reports_unneeded_clones() {
+        assert_eq!(
+            messages(vec![
+                Instruction::Clone {
+                    dst: reg(1),
+                    src: reg(0),
+                },
+                Instruction::Drop { src: reg(1) },
+                Instruction::Clone {
+                    dst: reg(2),
+                    src: reg(0),
+                },
+                Instruction::Return { src: reg(2) },
+            ]),
+            vec![
+                (Some(0), "the clone in %1 is never used".into()),
+                (
+                    Some(2),
+                    "%0 is never used again, so this could be a move".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unneeded_moves() {
+        assert_eq!(
+            messages(vec![
+                Instruction::Move {
+                    dst: reg(0),
+                    src: reg(0),
+                },
+                Instruction::Move {
+                    dst: reg(1),
+                    src: reg(0),
+                },
+                Instruction::Move {
+                    dst: reg(2),
+                    src: reg(1),
+                },
+                Instruction::Return { src: reg(2) },
+            ]),
+            vec![
+                (Some(0), "moves a register to itself".into()),
+                (Some(1), "%0 → %1 → %2 (at 2) could be a single move".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_unused_literals_and_collects() {
+        assert_eq!(
+            messages(vec![
+                Instruction::LoadLiteral {
+                    dst: reg(1),
+                    lit: Literal::Int(1),
+                },
+                Instruction::Drop { src: reg(1) },
+                Instruction::Collect { src_dst: reg(0) },
+                Instruction::Collect { src_dst: reg(0) },
+                Instruction::Return { src: reg(0) },
+            ]),
+            vec![
+                (
+                    Some(0),
+                    "literal in %1 is dropped at 1 without being used".into()
+                ),
+                (Some(2), "%0 is collected again immediately".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn accepts_needed_clone() {
+        assert_eq!(
+            messages(vec![
+                Instruction::Clone {
+                    dst: reg(1),
+                    src: reg(0),
+                },
+                Instruction::StoreVariable {
+                    var_id: VarId::new(100),
+                    src: reg(1),
+                },
+                Instruction::Return { src: reg(0) },
+            ]),
+            vec![]
+        );
+    }
+}
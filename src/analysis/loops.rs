@@ -0,0 +1,143 @@
+use std::collections::VecDeque;
+
+use nu_protocol::ir::Instruction;
+
+use super::Cfg;
+
+/// A loop found from the back edges in the control flow graph
+pub struct Loop {
+    /// The instruction at the start of the loop that the back edges jump to
+    pub header: usize,
+    /// Where the loop ends up when it's done, if the header is an `Iterate`. This is at most the
+    /// number of instructions, even if the `Iterate` jumps further.
+    pub exit: Option<usize>,
+    /// The instructions in the body of the loop, including the header, in order
+    pub body: Vec<usize>,
+    /// The innermost other loop that this one is nested in
+    pub parent: Option<usize>,
+}
+
+impl Loop {
+    /// How many other loops this one is nested in, given all of the loops in the block
+    pub fn depth(&self, loops: &[Loop]) -> usize {
+        let mut depth = 0;
+        let mut parent = self.parent;
+        while let Some(ancestor) = parent {
+            depth += 1;
+            parent = loops[ancestor].parent;
+        }
+        depth
+    }
+}
+
+/// Find the loops in a block, ordered so that each loop comes before the loops nested inside of it
+pub fn loops(instructions: &[Instruction]) -> Vec<Loop> {
+    let cfg = Cfg::new(instructions);
+    let reachable = cfg.reachable();
+
+    // A jump backwards (or to itself) is a back edge, and the loop consists of everything that can
+    // reach it without going through the header again
+    let mut in_body: Vec<(usize, Vec<bool>)> = vec![];
+    for (index, edges) in cfg.successors.iter().enumerate() {
+        if !reachable[index] {
+            continue;
+        }
+        for edge in edges.iter().filter(|edge| edge.target <= index) {
+            let header = edge.target;
+            let body = match in_body.iter_mut().find(|(other, _)| *other == header) {
+                Some((_, body)) => body,
+                None => {
+                    in_body.push((header, vec![false; cfg.len()]));
+                    &mut in_body.last_mut().expect("just pushed").1
+                }
+            };
+            body[header] = true;
+            let mut queue = VecDeque::from([index]);
+            while let Some(node) = queue.pop_front() {
+                if !std::mem::replace(&mut body[node], true) {
+                    queue.extend(
+                        cfg.predecessors[node]
+                            .iter()
+                            .filter(|&&pred| reachable[pred]),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut loops: Vec<Loop> = in_body
+        .into_iter()
+        .map(|(header, body)| Loop {
+            header,
+            exit: match instructions[header] {
+                Instruction::Iterate { end_index, .. } => Some(end_index.min(instructions.len())),
+                _ => None,
+            },
+            body: (0..body.len()).filter(|&index| body[index]).collect(),
+            parent: None,
+        })
+        .collect();
+    loops.sort_by_key(|l| (l.header, std::cmp::Reverse(l.body.len())));
+
+    // The parent is the smallest loop that contains the whole body
+    for index in 0..loops.len() {
+        loops[index].parent = (0..loops.len())
+            .filter(|&other| {
+                other != index
+                    && loops[other].body.len() > loops[index].body.len()
+                    && loops[index]
+                        .body
+                        .iter()
+                        .all(|inst| loops[other].body.binary_search(inst).is_ok())
+            })
+            .min_by_key(|&other| loops[other].body.len());
+    }
+
+    loops
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{ir::Instruction, ir::Literal, RegId};
+
+    use super::*;
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    /// `for x in [] { }`, with the `Iterate` ending at `end_index`
+    fn for_loop(end_index: usize) -> Vec<Instruction> {
+        vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::List { capacity: 0 },
+            },
+            Instruction::Iterate {
+                dst: reg(0),
+                stream: reg(1),
+                end_index,
+            },
+            Instruction::Drain { src: reg(0) },
+            Instruction::Jump { index: 1 },
+            Instruction::Return { src: reg(0) },
+        ]
+    }
+
+    #[test]
+    fn finds_iterate_loop() {
+        let loops = loops(&for_loop(4));
+        assert_eq!(loops.len(), 1);
+        assert_eq!(loops[0].header, 1);
+        assert_eq!(loops[0].exit, Some(4));
+        assert_eq!(loops[0].body, vec![1, 2, 3]);
+        assert_eq!(loops[0].parent, None);
+        assert_eq!(loops[0].depth(&loops), 0);
+    }
+
+    #[test]
+    fn clamps_exit_past_the_end() {
+        let loops = loops(&for_loop(100));
+        assert_eq!(loops[0].exit, Some(5));
+    }
+}
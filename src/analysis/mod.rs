@@ -0,0 +1,96 @@
+//! Static analysis of the instructions in an [`IrBlock`](nu_protocol::ir::IrBlock)
+
+mod calls;
+mod cfg;
+mod consts;
+mod env;
+mod handlers;
+mod lint;
+mod loops;
+mod provenance;
+mod reachability;
+pub mod registers;
+mod slices;
+mod ssa;
+mod stats;
+mod types;
+mod verify;
+
+pub use calls::*;
+pub use cfg::*;
+pub use consts::*;
+pub use env::*;
+pub use handlers::*;
+pub use lint::*;
+pub use loops::*;
+pub use provenance::*;
+pub use reachability::*;
+pub use slices::*;
+pub use ssa::*;
+pub use stats::*;
+pub use types::*;
+pub use verify::*;
+
+/// Helpers for building blocks by hand in tests
+#[cfg(test)]
+pub(crate) mod test_util {
+    use std::sync::Arc;
+
+    use nu_protocol::{
+        engine::EngineState,
+        ir::{Instruction, IrBlock},
+        BlockId, Span,
+    };
+
+    use crate::data::ViewIrOutput;
+
+    /// Make a block from instructions, with enough registers for all of them
+    pub fn ir_block(instructions: Vec<Instruction>) -> IrBlock {
+        ir_block_with_data(instructions, b"")
+    }
+
+    /// Make a block from instructions and the data that their slices point into
+    pub fn ir_block_with_data(instructions: Vec<Instruction>, data: &[u8]) -> IrBlock {
+        let register_count = instructions
+            .iter()
+            .flat_map(|instruction| {
+                super::registers::uses(instruction)
+                    .into_iter()
+                    .chain(super::registers::defs(instruction))
+            })
+            .map(|reg_id| reg_id.get() + 1)
+            .max()
+            .unwrap_or(0);
+        let len = instructions.len();
+        IrBlock {
+            instructions,
+            spans: vec![Span::unknown(); len],
+            data: Arc::from(data),
+            ast: vec![None; len],
+            comments: vec!["".into(); len],
+            register_count,
+            file_count: 0,
+        }
+    }
+
+    /// Wrap a block like `view ir` does. The instructions can't have calls, since there are no
+    /// declarations to name.
+    pub fn view_ir(ir_block: IrBlock) -> ViewIrOutput {
+        let engine_state = EngineState::new();
+        let formatted_instructions = ir_block
+            .instructions
+            .iter()
+            .map(|instruction| {
+                instruction
+                    .display(&engine_state, &ir_block.data)
+                    .to_string()
+            })
+            .collect();
+        ViewIrOutput {
+            block_id: BlockId::new(0),
+            span: None,
+            ir_block,
+            formatted_instructions,
+        }
+    }
+}
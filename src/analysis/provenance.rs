@@ -0,0 +1,238 @@
+use nu_protocol::{ir::Instruction, RegId};
+
+use super::{call_setup_ranges, is_call_setup, registers, Ssa, SsaValue};
+
+/// Where a value in a register came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// The input to the block, in `%0` at the start
+    BlockInput,
+    /// Nothing was written to the register before it was read
+    Empty,
+    /// Different paths meet with different values at the start of the instruction
+    Phi(usize),
+    /// The instruction wrote the value
+    Instruction(usize),
+    /// The value was stored to a variable that the instruction loads, by a `store-variable`
+    Store(usize),
+    /// The value was passed to the call as an argument by the instruction
+    Argument(usize),
+}
+
+/// A tree of the instructions that produced a value, where the children of each node are the
+/// values it was made from
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub reg_id: RegId,
+    pub source: Source,
+    /// The same source is already expanded earlier in the tree, so it isn't expanded again
+    pub repeated: bool,
+    pub children: Vec<Provenance>,
+}
+
+/// Trace the values of the registers read by an instruction back through everything that
+/// produced them. Each source is only expanded the first time it appears, which also stops the
+/// trace from going around loops forever.
+pub fn provenance(instructions: &[Instruction], ssa: &Ssa, index: usize) -> Vec<Provenance> {
+    let tracer = Tracer {
+        instructions,
+        ssa,
+        call_ranges: call_setup_ranges(instructions),
+    };
+    let mut expanded = vec![];
+    registers::uses(&instructions[index])
+        .into_iter()
+        .filter_map(|reg_id| {
+            let value = ssa.value(index, reg_id)?;
+            Some(tracer.value(reg_id, value, &mut expanded))
+        })
+        .collect()
+}
+
+struct Tracer<'a> {
+    instructions: &'a [Instruction],
+    ssa: &'a Ssa,
+    call_ranges: Vec<std::ops::Range<usize>>,
+}
+
+impl Tracer<'_> {
+    fn value(&self, reg_id: RegId, value: SsaValue, expanded: &mut Vec<Source>) -> Provenance {
+        let source = match value {
+            SsaValue::Entry if reg_id.get() == 0 => Source::BlockInput,
+            SsaValue::Entry => Source::Empty,
+            SsaValue::Phi(index) => Source::Phi(index),
+            SsaValue::Def(index) => Source::Instruction(index),
+        };
+        self.node(reg_id, source, expanded)
+    }
+
+    /// Trace the register that an instruction read, if it's reachable
+    fn read(&self, index: usize, reg_id: RegId, expanded: &mut Vec<Source>) -> Option<Provenance> {
+        let value = self.ssa.value(index, reg_id)?;
+        Some(self.value(reg_id, value, expanded))
+    }
+
+    fn node(&self, reg_id: RegId, source: Source, expanded: &mut Vec<Source>) -> Provenance {
+        if expanded.contains(&source) {
+            return Provenance {
+                reg_id,
+                source,
+                repeated: true,
+                children: vec![],
+            };
+        }
+        expanded.push(source);
+
+        let children = match source {
+            Source::BlockInput | Source::Empty => vec![],
+            Source::Phi(index) => self
+                .ssa
+                .phis
+                .iter()
+                .filter(|phi| phi.index == index && phi.reg_id == reg_id)
+                .flat_map(|phi| &phi.incoming)
+                .map(|(_, value)| self.value(reg_id, *value, expanded))
+                .collect(),
+            Source::Store(index) | Source::Argument(index) => {
+                registers::uses(&self.instructions[index])
+                    .into_iter()
+                    .filter_map(|reg_id| self.read(index, reg_id, expanded))
+                    .collect()
+            }
+            Source::Instruction(index) => {
+                let instruction = &self.instructions[index];
+                let mut children: Vec<Provenance> = registers::uses(instruction)
+                    .into_iter()
+                    .filter_map(|reg_id| self.read(index, reg_id, expanded))
+                    .collect();
+                match instruction {
+                    // A variable could have been stored by any `store-variable` in the block
+                    Instruction::LoadVariable { var_id, .. } => {
+                        for (store, _) in
+                            self.instructions.iter().enumerate().filter(|(_, inst)| {
+                                matches!(inst, Instruction::StoreVariable { var_id: id, .. }
+                                if id == var_id)
+                            })
+                        {
+                            if self.ssa.values[store].is_some() {
+                                children.push(self.node(reg_id, Source::Store(store), expanded));
+                            }
+                        }
+                    }
+                    // The output of a call depends on its arguments
+                    Instruction::Call { .. } => {
+                        if let Some(range) = self.call_ranges.iter().find(|r| r.end == index + 1) {
+                            for arg in range.clone().filter(|&arg| {
+                                is_call_setup(&self.instructions[arg])
+                                    && !registers::uses(&self.instructions[arg]).is_empty()
+                            }) {
+                                children.push(self.node(reg_id, Source::Argument(arg), expanded));
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                children
+            }
+        };
+
+        Provenance {
+            reg_id,
+            source,
+            repeated: false,
+            children,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{
+        ast::{Math, Operator},
+        ir::Literal,
+        VarId,
+    };
+
+    use super::*;
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    /// Flatten a tree to its sources, depth first
+    fn sources(tree: &[Provenance]) -> Vec<(Source, bool)> {
+        tree.iter()
+            .flat_map(|node| {
+                std::iter::once((node.source, node.repeated)).chain(sources(&node.children))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn traces_through_variables() {
+        let var_id = VarId::new(100);
+        let instructions = vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(1),
+            },
+            Instruction::StoreVariable {
+                var_id,
+                src: reg(1),
+            },
+            Instruction::LoadVariable {
+                dst: reg(2),
+                var_id,
+            },
+            Instruction::Return { src: reg(2) },
+        ];
+        let ssa = Ssa::new(&instructions, 3);
+        assert_eq!(
+            sources(&provenance(&instructions, &ssa, 3)),
+            vec![
+                (Source::Instruction(2), false),
+                (Source::Store(1), false),
+                (Source::Instruction(0), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_going_around_loops() {
+        // `$x = $x + $x` in a loop
+        let instructions = vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::List { capacity: 0 },
+            },
+            Instruction::Iterate {
+                dst: reg(2),
+                stream: reg(1),
+                end_index: 5,
+            },
+            Instruction::Clone {
+                dst: reg(2),
+                src: reg(0),
+            },
+            Instruction::BinaryOp {
+                lhs_dst: reg(0),
+                op: Operator::Math(Math::Plus),
+                rhs: reg(2),
+            },
+            Instruction::Jump { index: 1 },
+            Instruction::Return { src: reg(0) },
+        ];
+        let ssa = Ssa::new(&instructions, 3);
+        assert_eq!(
+            sources(&provenance(&instructions, &ssa, 5)),
+            vec![
+                (Source::Phi(1), false),
+                (Source::BlockInput, false),
+                (Source::Instruction(3), false),
+                (Source::Phi(1), true),
+                (Source::Instruction(2), false),
+                (Source::Phi(1), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,40 @@
+use nu_protocol::ir::Instruction;
+
+use super::Cfg;
+
+/// Find the instructions that can't be reached from the first instruction, along any edge
+/// including error handlers, and explain why for each one
+pub fn unreachable(instructions: &[Instruction]) -> Vec<Option<String>> {
+    let cfg = Cfg::new(instructions);
+    let reachable = cfg.reachable();
+
+    (0..instructions.len())
+        .map(|index| {
+            if reachable[index] {
+                return None;
+            }
+            let predecessors = &cfg.predecessors[index];
+            Some(if predecessors.is_empty() {
+                match index.checked_sub(1).map(|prev| &instructions[prev]) {
+                    Some(Instruction::Jump { .. }) => {
+                        "nothing jumps here, and the previous instruction always jumps away".into()
+                    }
+                    Some(Instruction::Return { .. } | Instruction::ReturnEarly { .. }) => {
+                        "nothing jumps here, and the previous instruction returns".into()
+                    }
+                    Some(Instruction::Unreachable) => {
+                        "nothing jumps here, and the previous instruction is `unreachable`".into()
+                    }
+                    _ => "nothing jumps here".into(),
+                }
+            } else {
+                let list = predecessors
+                    .iter()
+                    .map(|index| index.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("only reached from instructions that are unreachable themselves ({list})")
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,256 @@
+use nu_protocol::{
+    ir::{Instruction, Literal},
+    RegId,
+};
+
+use super::{Cfg, EdgeKind};
+
+/// Get the registers that an instruction reads from
+pub fn uses(instruction: &Instruction) -> Vec<RegId> {
+    match *instruction {
+        Instruction::LoadLiteral {
+            lit: Literal::Range {
+                start, step, end, ..
+            },
+            ..
+        } => vec![start, step, end],
+        Instruction::Move { src, .. }
+        | Instruction::Clone { src, .. }
+        | Instruction::Drop { src }
+        | Instruction::Drain { src }
+        | Instruction::DrainIfEnd { src }
+        | Instruction::StoreVariable { src, .. }
+        | Instruction::StoreEnv { src, .. }
+        | Instruction::PushPositional { src }
+        | Instruction::AppendRest { src }
+        | Instruction::PushNamed { src, .. }
+        | Instruction::PushShortNamed { src, .. }
+        | Instruction::CheckErrRedirected { src }
+        | Instruction::WriteFile { src, .. }
+        | Instruction::BranchIfEmpty { src, .. }
+        | Instruction::Match { src, .. }
+        | Instruction::CheckMatchGuard { src }
+        | Instruction::ReturnEarly { src }
+        | Instruction::Return { src } => vec![src],
+        Instruction::Collect { src_dst }
+        | Instruction::Span { src_dst }
+        | Instruction::Call { src_dst, .. }
+        | Instruction::GlobFrom { src_dst, .. }
+        | Instruction::Not { src_dst } => vec![src_dst],
+        Instruction::OpenFile { path, .. } => vec![path],
+        Instruction::StringAppend { src_dst, val } => vec![src_dst, val],
+        Instruction::ListPush { src_dst, item } => vec![src_dst, item],
+        Instruction::ListSpread { src_dst, items }
+        | Instruction::RecordSpread { src_dst, items } => {
+            vec![src_dst, items]
+        }
+        Instruction::RecordInsert { src_dst, key, val } => vec![src_dst, key, val],
+        Instruction::BinaryOp { lhs_dst, rhs, .. } => vec![lhs_dst, rhs],
+        Instruction::FollowCellPath { src_dst, path } => vec![src_dst, path],
+        Instruction::CloneCellPath { src, path, .. } => vec![src, path],
+        Instruction::UpsertCellPath {
+            src_dst,
+            path,
+            new_value,
+        } => vec![src_dst, path, new_value],
+        Instruction::BranchIf { cond, .. } => vec![cond],
+        Instruction::Iterate { stream, .. } => vec![stream],
+        _ => vec![],
+    }
+}
+
+/// Get the register that an instruction writes to when control flow leaves it along an edge of the
+/// given kind. `Iterate` only writes when it continues, and `OnErrorInto` only writes when the
+/// error handler is invoked.
+pub fn def(instruction: &Instruction, kind: EdgeKind) -> Option<RegId> {
+    match (instruction, kind) {
+        (Instruction::Iterate { .. }, EdgeKind::Branch) => None,
+        (Instruction::OnErrorInto { dst, .. }, EdgeKind::Error) => Some(*dst),
+        (_, EdgeKind::Error) => None,
+        _ => instruction.output_register(),
+    }
+}
+
+/// Get the registers that an instruction writes to along any edge
+pub fn defs(instruction: &Instruction) -> Vec<RegId> {
+    match instruction {
+        Instruction::OnErrorInto { dst, .. } => vec![*dst],
+        _ => instruction.output_register().into_iter().collect(),
+    }
+}
+
+/// Whether an instruction takes the value out of a register that it reads, leaving it empty unless
+/// it writes to the register again. A few only borrow or clone the value instead.
+pub fn takes(instruction: &Instruction, reg_id: RegId) -> bool {
+    let kept = match *instruction {
+        Instruction::Clone { src, .. }
+        | Instruction::CloneCellPath { src, .. }
+        | Instruction::CheckErrRedirected { src }
+        | Instruction::BranchIfEmpty { src, .. }
+        | Instruction::Match { src, .. }
+        | Instruction::CheckMatchGuard { src }
+        | Instruction::DrainIfEnd { src } => Some(src),
+        Instruction::Iterate { stream, .. } => Some(stream),
+        _ => None,
+    };
+    kept != Some(reg_id) && uses(instruction).contains(&reg_id)
+}
+
+/// Whether an instruction is expected to sometimes read a register that was never written, which
+/// holds `Empty`. The compiler relies on this for things like the input to a call.
+pub fn accepts_empty(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Call { .. }
+            | Instruction::Drop { .. }
+            | Instruction::Drain { .. }
+            | Instruction::DrainIfEnd { .. }
+            | Instruction::Collect { .. }
+            | Instruction::Span { .. }
+            | Instruction::Return { .. }
+            | Instruction::ReturnEarly { .. }
+            | Instruction::Move { .. }
+            | Instruction::BranchIfEmpty { .. }
+    )
+}
+
+/// Find which registers are live after each instruction, meaning that they might be read by
+/// `uses` at some later point before being written again
+pub fn live_out(
+    instructions: &[Instruction],
+    cfg: &Cfg,
+    uses: impl Fn(&Instruction) -> Vec<RegId>,
+    register_count: u32,
+) -> Vec<Vec<bool>> {
+    let register_count = register_count as usize;
+    let mut live_in = vec![vec![false; register_count]; instructions.len()];
+    let mut live_out = vec![vec![false; register_count]; instructions.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for index in (0..instructions.len()).rev() {
+            let instruction = &instructions[index];
+
+            let mut out = vec![false; register_count];
+            let mut new_in = vec![false; register_count];
+            for edge in &cfg.successors[index] {
+                let killed = def(instruction, edge.kind).map(|reg_id| reg_id.get() as usize);
+                for (reg, live) in live_in[edge.target].iter().enumerate() {
+                    if *live {
+                        out[reg] = true;
+                        if killed != Some(reg) {
+                            new_in[reg] = true;
+                        }
+                    }
+                }
+            }
+
+            for reg_id in uses(instruction) {
+                if let Some(live) = new_in.get_mut(reg_id.get() as usize) {
+                    *live = true;
+                }
+            }
+
+            if new_in != live_in[index] {
+                live_in[index] = new_in;
+                changed = true;
+            }
+            live_out[index] = out;
+        }
+    }
+
+    live_out
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::ast::{Math, Operator};
+
+    use super::*;
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    #[test]
+    fn finds_uses_and_defs() {
+        let add = Instruction::BinaryOp {
+            lhs_dst: reg(1),
+            op: Operator::Math(Math::Plus),
+            rhs: reg(2),
+        };
+        assert_eq!(uses(&add), vec![reg(1), reg(2)]);
+        assert_eq!(defs(&add), vec![reg(1)]);
+        assert!(takes(&add, reg(2)));
+
+        let clone = Instruction::Clone {
+            dst: reg(3),
+            src: reg(1),
+        };
+        assert_eq!(uses(&clone), vec![reg(1)]);
+        assert!(!takes(&clone, reg(1)));
+    }
+
+    #[test]
+    fn writes_depending_on_the_edge() {
+        let iterate = Instruction::Iterate {
+            dst: reg(1),
+            stream: reg(2),
+            end_index: 5,
+        };
+        assert_eq!(def(&iterate, EdgeKind::Next), Some(reg(1)));
+        assert_eq!(def(&iterate, EdgeKind::Branch), None);
+        assert!(!takes(&iterate, reg(2)));
+
+        let on_error = Instruction::OnErrorInto {
+            index: 5,
+            dst: reg(1),
+        };
+        assert_eq!(def(&on_error, EdgeKind::Next), None);
+        assert_eq!(def(&on_error, EdgeKind::Error), Some(reg(1)));
+        assert_eq!(defs(&on_error), vec![reg(1)]);
+    }
+
+    #[test]
+    fn finds_live_registers() {
+        let instructions = vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(1),
+            },
+            Instruction::LoadLiteral {
+                dst: reg(2),
+                lit: Literal::Int(2),
+            },
+            Instruction::Drop { src: reg(2) },
+            Instruction::Return { src: reg(1) },
+        ];
+        let live_out = live_out(&instructions, &Cfg::new(&instructions), uses, 3);
+        assert_eq!(live_out[0], vec![false, true, false]);
+        assert_eq!(live_out[1], vec![false, true, true]);
+        assert_eq!(live_out[2], vec![false, true, false]);
+        assert_eq!(live_out[3], vec![false, false, false]);
+    }
+
+    #[test]
+    fn carries_liveness_around_loops() {
+        let instructions = vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::List { capacity: 0 },
+            },
+            Instruction::Iterate {
+                dst: reg(2),
+                stream: reg(1),
+                end_index: 4,
+            },
+            Instruction::Drop { src: reg(2) },
+            Instruction::Jump { index: 1 },
+            Instruction::Return { src: reg(0) },
+        ];
+        let live_out = live_out(&instructions, &Cfg::new(&instructions), uses, 3);
+        // The stream is still needed at the jump back to the `Iterate`
+        assert_eq!(live_out[3], vec![true, true, false]);
+    }
+}
@@ -0,0 +1,53 @@
+use nu_protocol::ir::{DataSlice, IrBlock};
+
+/// Get the bytes of a slice of the block's data, or `None` if it's out of range
+pub fn data_slice(ir_block: &IrBlock, slice: DataSlice) -> Option<&[u8]> {
+    let start = slice.start as usize;
+    ir_block.data.get(start..start + slice.len as usize)
+}
+
+/// Get a string from the block's data, or `None` if it's out of range or isn't valid UTF-8
+pub fn data_str(ir_block: &IrBlock, slice: DataSlice) -> Option<&str> {
+    std::str::from_utf8(data_slice(ir_block, slice)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::test_util::ir_block_with_data;
+
+    #[test]
+    fn reads_slices_in_range() {
+        let ir_block = ir_block_with_data(vec![], b"abc\xff");
+        assert_eq!(
+            data_slice(&ir_block, DataSlice { start: 1, len: 2 }),
+            Some(&b"bc"[..])
+        );
+        assert_eq!(
+            data_str(&ir_block, DataSlice { start: 0, len: 3 }),
+            Some("abc")
+        );
+        assert_eq!(
+            data_str(&ir_block, DataSlice { start: 4, len: 0 }),
+            Some("")
+        );
+    }
+
+    #[test]
+    fn rejects_bad_slices() {
+        let ir_block = ir_block_with_data(vec![], b"abc\xff");
+        assert_eq!(data_slice(&ir_block, DataSlice { start: 2, len: 3 }), None);
+        assert_eq!(data_slice(&ir_block, DataSlice { start: 5, len: 0 }), None);
+        assert_eq!(
+            data_slice(
+                &ir_block,
+                DataSlice {
+                    start: u32::MAX,
+                    len: u32::MAX
+                }
+            ),
+            None
+        );
+        assert_eq!(data_str(&ir_block, DataSlice { start: 2, len: 2 }), None);
+    }
+}
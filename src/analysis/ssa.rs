@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use nu_protocol::{ir::Instruction, RegId};
+
+use super::{registers, Cfg, Edge};
+
+/// Where the value in a register came from, in SSA form
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SsaValue {
+    /// The value when the block started, which is the input for `%0` and empty otherwise
+    Entry,
+    /// A phi node at the start of the instruction, where paths with different values meet
+    Phi(usize),
+    /// Written by the instruction
+    Def(usize),
+}
+
+/// A phi node that picks the value of a register depending on where control flow came from
+pub struct Phi {
+    pub index: usize,
+    pub reg_id: RegId,
+    /// The value coming from each predecessor, or from the start of the block for `None`
+    pub incoming: Vec<(Option<usize>, SsaValue)>,
+}
+
+/// The block in SSA form, where each write to a register is a separately numbered value
+pub struct Ssa {
+    /// The value of each register on entry to each instruction, after any phi nodes, or `None` if
+    /// the instruction is unreachable
+    pub values: Vec<Option<Vec<SsaValue>>>,
+    /// Phi nodes where they're needed for a register that is still going to be used, in order
+    pub phis: Vec<Phi>,
+    versions: HashMap<(RegId, SsaValue), usize>,
+}
+
+impl Ssa {
+    pub fn new(instructions: &[Instruction], register_count: u32) -> Ssa {
+        let cfg = Cfg::new(instructions);
+
+        let transfer = |index: usize, values: &Vec<SsaValue>, edge: Edge| {
+            let mut values = values.clone();
+            if let Some(reg_id) = registers::def(&instructions[index], edge.kind) {
+                if let Some(value) = values.get_mut(reg_id.get() as usize) {
+                    *value = SsaValue::Def(index);
+                }
+            }
+            values
+        };
+
+        let values = cfg.forward(
+            vec![SsaValue::Entry; register_count as usize],
+            transfer,
+            |target, existing, out| {
+                // The start of the block counts as another path into the first instruction
+                if target != 0 && cfg.predecessors[target].len() <= 1 {
+                    return out.clone();
+                }
+                existing
+                    .iter()
+                    .zip(out)
+                    .map(|(a, b)| if a == b { *a } else { SsaValue::Phi(target) })
+                    .collect()
+            },
+        );
+
+        // Only keep phi nodes for registers that are live
+        let live_out = registers::live_out(instructions, &cfg, registers::uses, register_count);
+        let mut phis = vec![];
+        for (index, entry) in values.iter().enumerate() {
+            let Some(entry) = entry else {
+                continue;
+            };
+            let instruction = &instructions[index];
+            for (reg, value) in entry.iter().enumerate() {
+                let reg_id = RegId::new(reg as u32);
+                let live_in = registers::uses(instruction).contains(&reg_id)
+                    || (live_out[index][reg] && !registers::defs(instruction).contains(&reg_id));
+                if *value != SsaValue::Phi(index) || !live_in {
+                    continue;
+                }
+                let mut incoming: Vec<(Option<usize>, SsaValue)> = cfg.predecessors[index]
+                    .iter()
+                    .filter_map(|&pred| {
+                        let edge = cfg.successors[pred]
+                            .iter()
+                            .find(|edge| edge.target == index)?;
+                        let out = transfer(pred, values[pred].as_ref()?, *edge);
+                        Some((Some(pred), out[reg]))
+                    })
+                    .collect();
+                if index == 0 {
+                    incoming.insert(0, (None, SsaValue::Entry));
+                }
+                phis.push(Phi {
+                    index,
+                    reg_id,
+                    incoming,
+                });
+            }
+        }
+
+        // Number the values of each register in the order they appear
+        let mut versions = HashMap::new();
+        let mut next_version = vec![1; register_count as usize];
+        let mut phi_iter = phis.iter().peekable();
+        for (index, instruction) in instructions.iter().enumerate() {
+            while let Some(phi) = phi_iter.next_if(|phi| phi.index == index) {
+                let reg = phi.reg_id.get() as usize;
+                versions.insert((phi.reg_id, SsaValue::Phi(index)), next_version[reg]);
+                next_version[reg] += 1;
+            }
+            for reg_id in registers::defs(instruction) {
+                if let Some(version) = next_version.get_mut(reg_id.get() as usize) {
+                    versions.insert((reg_id, SsaValue::Def(index)), *version);
+                    *version += 1;
+                }
+            }
+        }
+
+        Ssa {
+            values,
+            phis,
+            versions,
+        }
+    }
+
+    /// Get the value of a register on entry to an instruction
+    pub fn value(&self, index: usize, reg_id: RegId) -> Option<SsaValue> {
+        self.values
+            .get(index)?
+            .as_ref()?
+            .get(reg_id.get() as usize)
+            .copied()
+    }
+
+    /// Get the name of a value in a register, like `%1.2` for the second value written to `%1`
+    pub fn name(&self, reg_id: RegId, value: SsaValue) -> String {
+        match value {
+            SsaValue::Entry => format!("{reg_id}.0"),
+            _ => match self.versions.get(&(reg_id, value)) {
+                Some(version) => format!("{reg_id}.{version}"),
+                None => format!("{reg_id}.?"),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{
+        ast::{Math, Operator},
+        ir::Literal,
+    };
+
+    use super::*;
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    /// `mut x = 0; for _ in [] { $x += 1 }; $x`
+    fn counting_loop() -> Vec<Instruction> {
+        vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(0),
+            },
+            Instruction::LoadLiteral {
+                dst: reg(2),
+                lit: Literal::List { capacity: 0 },
+            },
+            Instruction::Iterate {
+                dst: reg(3),
+                stream: reg(2),
+                end_index: 7,
+            },
+            Instruction::LoadLiteral {
+                dst: reg(4),
+                lit: Literal::Int(1),
+            },
+            Instruction::BinaryOp {
+                lhs_dst: reg(1),
+                op: Operator::Math(Math::Plus),
+                rhs: reg(4),
+            },
+            Instruction::Drop { src: reg(3) },
+            Instruction::Jump { index: 2 },
+            Instruction::Return { src: reg(1) },
+        ]
+    }
+
+    #[test]
+    fn places_phi_at_loop_header() {
+        let ssa = Ssa::new(&counting_loop(), 5);
+        // `%4` is also written in the loop, but is dead at the header, and `%2` and `%3` are the
+        // same on both paths
+        assert_eq!(ssa.phis.len(), 1);
+        let phi = &ssa.phis[0];
+        assert_eq!(phi.index, 2);
+        assert_eq!(phi.reg_id, reg(1));
+        assert_eq!(
+            phi.incoming,
+            vec![(Some(1), SsaValue::Def(0)), (Some(6), SsaValue::Def(4))]
+        );
+    }
+
+    #[test]
+    fn reads_phi_after_loop() {
+        let ssa = Ssa::new(&counting_loop(), 5);
+        assert_eq!(ssa.value(4, reg(1)), Some(SsaValue::Phi(2)));
+        assert_eq!(ssa.value(7, reg(1)), Some(SsaValue::Phi(2)));
+        assert_eq!(ssa.name(reg(1), SsaValue::Def(0)), "%1.1");
+        assert_eq!(ssa.name(reg(1), SsaValue::Phi(2)), "%1.2");
+        assert_eq!(ssa.name(reg(1), SsaValue::Def(4)), "%1.3");
+        assert_eq!(ssa.name(reg(4), SsaValue::Entry), "%4.0");
+    }
+
+    #[test]
+    fn has_no_values_for_unreachable_code() {
+        let instructions = vec![
+            Instruction::Return { src: reg(0) },
+            Instruction::Return { src: reg(0) },
+        ];
+        let ssa = Ssa::new(&instructions, 1);
+        assert_eq!(ssa.value(0, reg(0)), Some(SsaValue::Entry));
+        assert_eq!(ssa.value(1, reg(0)), None);
+    }
+
+    #[test]
+    fn ignores_register_out_of_range() {
+        let instructions = vec![
+            Instruction::LoadLiteral {
+                dst: reg(3),
+                lit: Literal::Nothing,
+            },
+            Instruction::Return { src: reg(3) },
+        ];
+        let ssa = Ssa::new(&instructions, 1);
+        assert_eq!(ssa.value(1, reg(3)), None);
+    }
+}
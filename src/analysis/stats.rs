@@ -0,0 +1,166 @@
+use nu_protocol::ir::{Instruction, IrBlock};
+
+use super::{loops, Cfg};
+
+/// Measurements of how big and complicated a block is
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub instructions: usize,
+    pub basic_blocks: usize,
+    /// The number of independent paths through the block, counted as one more than the number of
+    /// extra ways out of each reachable instruction
+    pub cyclomatic_complexity: usize,
+    /// How deeply loops are nested, or zero if there aren't any loops
+    pub max_loop_depth: usize,
+    pub calls: usize,
+    pub registers: usize,
+    /// The number of error handlers pushed, by `try` blocks
+    pub error_handlers: usize,
+    /// The size of the data for strings and other literals, in bytes
+    pub data_size: usize,
+}
+
+pub fn stats(ir_block: &IrBlock) -> Stats {
+    let instructions = &ir_block.instructions;
+    let cfg = Cfg::new(instructions);
+    let reachable = cfg.reachable();
+    let loops = loops(instructions);
+
+    let basic_blocks = (0..instructions.len())
+        .filter(|&index| index == 0 || cfg.straight_line_next(index - 1) != Some(index))
+        .count();
+
+    let cyclomatic_complexity = 1
+        + (0..instructions.len())
+            .filter(|&index| reachable[index])
+            .map(|index| cfg.successors[index].len().saturating_sub(1))
+            .sum::<usize>();
+
+    let max_loop_depth = loops
+        .iter()
+        .map(|the_loop| the_loop.depth(&loops) + 1)
+        .max()
+        .unwrap_or(0);
+
+    let count = |predicate: fn(&Instruction) -> bool| {
+        instructions
+            .iter()
+            .filter(|instruction| predicate(instruction))
+            .count()
+    };
+
+    Stats {
+        instructions: instructions.len(),
+        basic_blocks,
+        cyclomatic_complexity,
+        max_loop_depth,
+        calls: count(|instruction| matches!(instruction, Instruction::Call { .. })),
+        registers: ir_block.register_count as usize,
+        error_handlers: count(|instruction| {
+            matches!(
+                instruction,
+                Instruction::OnError { .. } | Instruction::OnErrorInto { .. }
+            )
+        }),
+        data_size: ir_block.data.len(),
+    }
+}
+
+/// Get the name of an instruction's opcode from the way it's formatted, like `load-literal`
+pub fn opcode(formatted: &str) -> &str {
+    formatted.split(' ').next().unwrap_or(formatted)
+}
+
+/// Count how many of each opcode there are in formatted instructions, most common first
+pub fn opcode_histogram<'a>(
+    formatted: impl IntoIterator<Item = &'a String>,
+) -> Vec<(String, usize)> {
+    let mut histogram: Vec<(String, usize)> = vec![];
+    for inst in formatted {
+        let name = opcode(inst);
+        match histogram.iter_mut().find(|(opcode, _)| opcode == name) {
+            Some((_, count)) => *count += 1,
+            None => histogram.push((name.to_owned(), 1)),
+        }
+    }
+    histogram.sort_by(|(a_name, a_count), (b_name, b_count)| {
+        b_count.cmp(a_count).then_with(|| a_name.cmp(b_name))
+    });
+    histogram
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{ir::Literal, RegId};
+
+    use super::*;
+    use crate::analysis::test_util::{ir_block, view_ir};
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    /// `for x in [] { for y in [] { } }`
+    fn nested_loops() -> Vec<Instruction> {
+        vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::List { capacity: 0 },
+            },
+            Instruction::Iterate {
+                dst: reg(2),
+                stream: reg(1),
+                end_index: 8,
+            },
+            Instruction::LoadLiteral {
+                dst: reg(3),
+                lit: Literal::List { capacity: 0 },
+            },
+            Instruction::Iterate {
+                dst: reg(4),
+                stream: reg(3),
+                end_index: 6,
+            },
+            Instruction::Drop { src: reg(4) },
+            Instruction::Jump { index: 3 },
+            Instruction::Drop { src: reg(2) },
+            Instruction::Jump { index: 1 },
+            Instruction::Return { src: reg(0) },
+        ]
+    }
+
+    #[test]
+    fn measures_nested_loops() {
+        let stats = stats(&ir_block(nested_loops()));
+        assert_eq!(stats.instructions, 9);
+        // The loop headers start new blocks: 0, 1, 2, 3, 4-5, 6-7, 8
+        assert_eq!(stats.basic_blocks, 7);
+        assert_eq!(stats.cyclomatic_complexity, 3);
+        assert_eq!(stats.max_loop_depth, 2);
+        assert_eq!(stats.registers, 5);
+        assert_eq!(stats.calls, 0);
+    }
+
+    #[test]
+    fn measures_empty_block() {
+        let stats = stats(&ir_block(vec![]));
+        assert_eq!(stats.basic_blocks, 0);
+        assert_eq!(stats.cyclomatic_complexity, 1);
+        assert_eq!(stats.max_loop_depth, 0);
+    }
+
+    #[test]
+    fn counts_opcodes() {
+        let view_ir = view_ir(ir_block(nested_loops()));
+        assert_eq!(
+            opcode_histogram(&view_ir.formatted_instructions),
+            vec![
+                ("drop".into(), 2),
+                ("iterate".into(), 2),
+                ("jump".into(), 2),
+                ("load-literal".into(), 2),
+                ("return".into(), 1),
+            ]
+        );
+    }
+}
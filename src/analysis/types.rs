@@ -0,0 +1,765 @@
+use std::{collections::HashMap, fmt};
+
+use nu_protocol::{
+    ast::{Math, Operator, PathMember},
+    ir::{Instruction, IrBlock, Literal},
+    DeclId, RegId, Type, VarId,
+};
+
+use super::{data_str, registers, Cfg, Edge, EdgeKind};
+
+/// More alternatives than this are widened to `any`
+const MAX_TYPES: usize = 4;
+
+/// Types nested deeper than this are widened to `any`, so that loops building nested lists still
+/// reach a fixed point
+const MAX_DEPTH: usize = 3;
+
+/// The input and output types of a command's signature
+pub type Signatures = HashMap<DeclId, Vec<(Type, Type)>>;
+
+/// The types that a register might hold. No types at all means that the register is empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TypeSet(Vec<Type>);
+
+impl TypeSet {
+    pub fn empty() -> TypeSet {
+        TypeSet(vec![])
+    }
+
+    pub fn any() -> TypeSet {
+        TypeSet(vec![Type::Any])
+    }
+
+    pub fn of(ty: Type) -> TypeSet {
+        TypeSet::empty().with(ty)
+    }
+
+    pub fn types(&self) -> &[Type] {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn is_any(&self) -> bool {
+        self.0 == [Type::Any]
+    }
+
+    /// Add a possible type
+    pub fn with(mut self, ty: Type) -> TypeSet {
+        let ty = limit_depth(ty, 0);
+        if self.is_any() || self.0.contains(&ty) {
+            return self;
+        }
+        if ty == Type::Any || self.0.len() == MAX_TYPES {
+            return TypeSet::any();
+        }
+        self.0.push(ty);
+        self
+    }
+
+    pub fn union(&self, other: &TypeSet) -> TypeSet {
+        other.0.iter().cloned().fold(self.clone(), TypeSet::with)
+    }
+
+    /// Get the one type that covers all of the alternatives, like nushell does for the items of a
+    /// list
+    pub fn supertype(&self) -> Type {
+        match &self.0[..] {
+            [] => Type::Nothing,
+            [ty] => ty.clone(),
+            types if types.iter().all(|ty| matches!(ty, Type::Int | Type::Float)) => Type::Number,
+            _ => Type::Any,
+        }
+    }
+}
+
+impl fmt::Display for TypeSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("empty");
+        }
+        for (index, ty) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{ty}")?;
+        }
+        Ok(())
+    }
+}
+
+fn limit_depth(ty: Type, depth: usize) -> Type {
+    match ty {
+        _ if depth > MAX_DEPTH => Type::Any,
+        Type::List(item) => Type::list(limit_depth(*item, depth + 1)),
+        Type::Record(fields) => Type::Record(
+            fields
+                .into_vec()
+                .into_iter()
+                .map(|(name, ty)| (name, limit_depth(ty, depth + 1)))
+                .collect(),
+        ),
+        ty => ty,
+    }
+}
+
+/// What is known about the types at a point in the block
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeState {
+    pub registers: Vec<TypeSet>,
+    /// The variables that have been stored to in this block. Other variables could be anything.
+    pub variables: Vec<(VarId, TypeSet)>,
+    /// The literal in each register, if it's known, so that record keys and cell paths can be
+    /// followed
+    constants: Vec<Option<Constant>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Constant {
+    String(String),
+    CellPath(Vec<PathMember>),
+}
+
+impl TypeState {
+    fn new(register_count: u32) -> TypeState {
+        let count = register_count as usize;
+        // The block's input could be anything
+        let mut registers = vec![TypeSet::empty(); count];
+        if let Some(input) = registers.first_mut() {
+            *input = TypeSet::any();
+        }
+        TypeState {
+            registers,
+            variables: vec![],
+            constants: vec![None; count],
+        }
+    }
+
+    fn join(&self, other: &TypeState) -> TypeState {
+        // A variable that wasn't stored to on one of the paths still has whatever it had before
+        // the block, which could be anything, so only keep the ones stored to on both
+        let variables = self
+            .variables
+            .iter()
+            .filter_map(|(var_id, types)| {
+                let (_, other_types) = other.variables.iter().find(|(id, _)| id == var_id)?;
+                Some((*var_id, types.union(other_types)))
+            })
+            .collect();
+        TypeState {
+            registers: self
+                .registers
+                .iter()
+                .zip(&other.registers)
+                .map(|(a, b)| a.union(b))
+                .collect(),
+            variables,
+            constants: self
+                .constants
+                .iter()
+                .zip(&other.constants)
+                .map(|(a, b)| if a == b { a.clone() } else { None })
+                .collect(),
+        }
+    }
+
+    pub fn register(&self, reg_id: RegId) -> TypeSet {
+        self.registers
+            .get(reg_id.get() as usize)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn variable(&self, var_id: VarId) -> TypeSet {
+        self.variables
+            .iter()
+            .find(|(id, _)| *id == var_id)
+            .map(|(_, types)| types.clone())
+            .unwrap_or_else(TypeSet::any)
+    }
+}
+
+/// The types inferred for a block
+pub struct Types {
+    /// The types on entry to each instruction, or `None` if it's unreachable
+    pub entry: Vec<Option<TypeState>>,
+    /// The register that each instruction writes, if any, and the types it could write
+    pub output: Vec<Option<(RegId, TypeSet)>>,
+}
+
+/// Infer the types that each register might hold at each instruction, with an abstract
+/// interpreter over the control flow graph. The output types of calls come from `signatures`.
+pub fn infer_types(ir_block: &IrBlock, signatures: &Signatures) -> Types {
+    let instructions = &ir_block.instructions;
+    let cfg = Cfg::new(instructions);
+    let transfer = |index: usize, state: &TypeState, edge: Edge| {
+        transfer(ir_block, signatures, index, state, edge)
+    };
+
+    let entry = cfg.forward(
+        TypeState::new(ir_block.register_count),
+        transfer,
+        |_, a, b| a.join(b),
+    );
+
+    let output = instructions
+        .iter()
+        .enumerate()
+        .map(|(index, instruction)| {
+            let state = entry[index].as_ref()?;
+            let reg_id = registers::def(instruction, EdgeKind::Next)?;
+            let edge = Edge {
+                target: index + 1,
+                kind: EdgeKind::Next,
+            };
+            Some((reg_id, transfer(index, state, edge).register(reg_id)))
+        })
+        .collect();
+
+    Types { entry, output }
+}
+
+fn transfer(
+    ir_block: &IrBlock,
+    signatures: &Signatures,
+    index: usize,
+    state: &TypeState,
+    edge: Edge,
+) -> TypeState {
+    let instruction = &ir_block.instructions[index];
+    let mut out = state.clone();
+
+    if edge.kind == EdgeKind::Error {
+        if let Instruction::OnErrorInto { dst, .. } = instruction {
+            set(&mut out, *dst, TypeSet::of(Type::record()), None);
+        }
+        return out;
+    }
+
+    // Registers that are read are emptied, unless the instruction keeps them
+    for reg_id in registers::uses(instruction) {
+        if registers::takes(instruction, reg_id) {
+            set(&mut out, reg_id, TypeSet::empty(), None);
+        }
+    }
+
+    let Some(dst) = registers::def(instruction, edge.kind) else {
+        if let Instruction::StoreVariable { var_id, src } = instruction {
+            let types = state.register(*src);
+            match out.variables.iter_mut().find(|(id, _)| id == var_id) {
+                Some((_, existing)) => *existing = types,
+                None => out.variables.push((*var_id, types)),
+            }
+        }
+        return out;
+    };
+
+    let constant = |reg_id: RegId| {
+        state
+            .constants
+            .get(reg_id.get() as usize)
+            .cloned()
+            .flatten()
+    };
+
+    let (types, constant) = match instruction {
+        Instruction::LoadLiteral { lit, .. } => (
+            TypeSet::of(literal_type(lit)),
+            match lit {
+                Literal::String(slice) | Literal::RawString(slice) => {
+                    data_str(ir_block, *slice).map(|string| Constant::String(string.into()))
+                }
+                Literal::CellPath(path) => Some(Constant::CellPath(path.members.clone())),
+                _ => None,
+            },
+        ),
+        Instruction::LoadValue { val, .. } => (TypeSet::of(val.get_type()), None),
+        Instruction::Move { src, .. } | Instruction::Clone { src, .. } => {
+            (state.register(*src), constant(*src))
+        }
+        Instruction::Span { src_dst } => (state.register(*src_dst), constant(*src_dst)),
+        Instruction::Collect { src_dst } => (
+            state
+                .register(*src_dst)
+                .types()
+                .iter()
+                .map(|ty| match ty {
+                    Type::ListStream => Type::list(Type::Any),
+                    ty => ty.clone(),
+                })
+                .fold(TypeSet::empty(), TypeSet::with),
+            constant(*src_dst),
+        ),
+        Instruction::LoadVariable { var_id, .. } => (state.variable(*var_id), None),
+        Instruction::Not { .. } => (TypeSet::of(Type::Bool), None),
+        Instruction::BinaryOp { lhs_dst, op, rhs } => {
+            let lhs = state.register(*lhs_dst);
+            let rhs = state.register(*rhs);
+            let mut types = TypeSet::empty();
+            for lhs in lhs.types() {
+                for rhs in rhs.types() {
+                    for ty in binary_op_types(op, lhs, rhs) {
+                        types = types.with(ty);
+                    }
+                }
+            }
+            (types, None)
+        }
+        Instruction::StringAppend { .. } => (TypeSet::of(Type::String), None),
+        Instruction::GlobFrom { .. } => (TypeSet::of(Type::Glob), None),
+        Instruction::ListPush { src_dst, item } => {
+            let item = state.register(*item);
+            (
+                map_lists(&state.register(*src_dst), |items| items.union(&item)),
+                None,
+            )
+        }
+        Instruction::ListSpread { src_dst, items } => {
+            let spread = state
+                .register(*items)
+                .types()
+                .iter()
+                .map(item_types)
+                .fold(TypeSet::empty(), |a, b| a.union(&b));
+            (
+                map_lists(&state.register(*src_dst), |items| items.union(&spread)),
+                None,
+            )
+        }
+        Instruction::RecordInsert { src_dst, key, val } => {
+            let key = match constant(*key) {
+                Some(Constant::String(key)) => Some(key),
+                _ => None,
+            };
+            let val = state.register(*val).supertype();
+            let types = state
+                .register(*src_dst)
+                .types()
+                .iter()
+                .map(|ty| match (ty, &key) {
+                    (Type::Record(fields), Some(key)) => {
+                        let mut fields = fields.to_vec();
+                        fields.retain(|(name, _)| name != key);
+                        fields.push((key.clone(), val.clone()));
+                        Type::Record(fields.into())
+                    }
+                    (Type::Record(_), None) => Type::record(),
+                    _ => Type::Any,
+                })
+                .fold(TypeSet::empty(), TypeSet::with);
+            (types, None)
+        }
+        Instruction::FollowCellPath { src_dst: src, path }
+        | Instruction::CloneCellPath { src, path, .. } => (
+            match constant(*path) {
+                Some(Constant::CellPath(members)) => state
+                    .register(*src)
+                    .types()
+                    .iter()
+                    .map(|ty| follow_cell_path(ty, &members))
+                    .fold(TypeSet::empty(), |a, b| a.union(&b)),
+                _ => TypeSet::any(),
+            },
+            None,
+        ),
+        Instruction::RecordSpread { .. } => (TypeSet::of(Type::record()), None),
+        Instruction::UpsertCellPath { src_dst, .. } => (state.register(*src_dst), None),
+        Instruction::Iterate { stream, .. } => (
+            state
+                .register(*stream)
+                .types()
+                .iter()
+                .map(|ty| match ty {
+                    Type::List(item) => (**item).clone(),
+                    Type::Table(fields) => Type::Record(fields.clone()),
+                    Type::Range => Type::Int,
+                    _ => Type::Any,
+                })
+                .fold(TypeSet::empty(), TypeSet::with),
+            None,
+        ),
+        Instruction::Call { decl_id, src_dst } => (
+            call_output(signatures, *decl_id, &state.register(*src_dst)),
+            None,
+        ),
+        _ => (TypeSet::any(), None),
+    };
+
+    set(&mut out, dst, types, constant);
+    out
+}
+
+fn set(state: &mut TypeState, reg_id: RegId, types: TypeSet, constant: Option<Constant>) {
+    let reg = reg_id.get() as usize;
+    if let Some(register) = state.registers.get_mut(reg) {
+        *register = types;
+        state.constants[reg] = constant;
+    }
+}
+
+/// Get the types of the items of a list or table, where no types means the list is empty
+fn item_types(ty: &Type) -> TypeSet {
+    match ty {
+        Type::List(item) if **item == Type::Nothing => TypeSet::empty(),
+        Type::List(item) => TypeSet::of((**item).clone()),
+        Type::Table(fields) => TypeSet::of(Type::Record(fields.clone())),
+        _ => TypeSet::any(),
+    }
+}
+
+/// Get the types of the value at the end of a cell path
+fn follow_cell_path(ty: &Type, members: &[PathMember]) -> TypeSet {
+    let Some((member, rest)) = members.split_first() else {
+        return TypeSet::of(ty.clone());
+    };
+    let (found, optional) = match (member, ty) {
+        (PathMember::String { val, optional, .. }, Type::Record(fields)) => (
+            fields
+                .iter()
+                .find(|(name, _)| name == val)
+                .map(|(_, ty)| follow_cell_path(ty, rest)),
+            *optional,
+        ),
+        (PathMember::String { val, optional, .. }, Type::Table(fields)) => (
+            fields
+                .iter()
+                .find(|(name, _)| name == val)
+                .map(|(_, ty)| TypeSet::of(Type::list(follow_cell_path(ty, rest).supertype()))),
+            *optional,
+        ),
+        (PathMember::Int { optional, .. }, Type::List(_) | Type::Table(_)) => (
+            Some(
+                item_types(ty)
+                    .types()
+                    .iter()
+                    .map(|item| follow_cell_path(item, rest))
+                    .fold(TypeSet::empty(), |a, b| a.union(&b)),
+            ),
+            *optional,
+        ),
+        _ => (None, false),
+    };
+    match found {
+        Some(types) if optional => types.with(Type::Nothing),
+        Some(types) => types,
+        None => TypeSet::any(),
+    }
+}
+
+/// Change the item type of each list type
+fn map_lists(types: &TypeSet, f: impl Fn(TypeSet) -> TypeSet) -> TypeSet {
+    types
+        .types()
+        .iter()
+        .map(|ty| match ty {
+            // A list literal starts out as `list<nothing>` until something is pushed to it
+            Type::List(_) => Type::list(f(item_types(ty)).supertype()),
+            _ => Type::Any,
+        })
+        .fold(TypeSet::empty(), TypeSet::with)
+}
+
+fn literal_type(lit: &Literal) -> Type {
+    match lit {
+        Literal::Bool(_) => Type::Bool,
+        Literal::Int(_) => Type::Int,
+        Literal::Float(_) => Type::Float,
+        Literal::Filesize(_) => Type::Filesize,
+        Literal::Duration(_) => Type::Duration,
+        Literal::Binary(_) => Type::Binary,
+        Literal::Block(_) => Type::Block,
+        Literal::Closure(_) | Literal::RowCondition(_) => Type::Closure,
+        Literal::Range { .. } => Type::Range,
+        Literal::List { .. } => Type::list(Type::Nothing),
+        Literal::Record { .. } => Type::record(),
+        Literal::Filepath { .. } | Literal::Directory { .. } => Type::String,
+        Literal::GlobPattern { .. } => Type::Glob,
+        Literal::String(_) | Literal::RawString(_) => Type::String,
+        Literal::CellPath(_) => Type::CellPath,
+        Literal::Date(_) => Type::Date,
+        Literal::Nothing => Type::Nothing,
+    }
+}
+
+/// The possible result types of a binary operator, following what nushell's operators do for the
+/// common cases and giving up with `any` otherwise
+fn binary_op_types(op: &Operator, lhs: &Type, rhs: &Type) -> Vec<Type> {
+    use Type::*;
+    let numeric = |ty: &Type| matches!(ty, Int | Float | Number);
+    match op {
+        Operator::Comparison(_) | Operator::Boolean(_) => vec![Bool],
+        Operator::Bits(_) => vec![Int],
+        Operator::Math(math) => match (math, lhs, rhs) {
+            (_, Any, _) | (_, _, Any) => vec![Any],
+            (Math::Append, List(_), List(_)) => {
+                vec![Type::list(
+                    item_types(lhs).union(&item_types(rhs)).supertype(),
+                )]
+            }
+            (Math::Append, List(_), item) => {
+                vec![Type::list(item_types(lhs).with(item.clone()).supertype())]
+            }
+            (Math::Append | Math::Plus, String, String) => vec![String],
+            (Math::Append, Binary, Binary) => vec![Binary],
+            (Math::Divide, Int, Int) => vec![Int, Float],
+            (Math::Divide, Duration, Duration) | (Math::Divide, Filesize, Filesize) => {
+                vec![Float]
+            }
+            (_, Int, Int) => vec![Int],
+            (_, a, b) if numeric(a) && numeric(b) => {
+                if matches!((a, b), (Number, _) | (_, Number)) {
+                    vec![Number]
+                } else {
+                    vec![Float]
+                }
+            }
+            (Math::Plus | Math::Minus, Date, Duration) => vec![Date],
+            (Math::Minus, Date, Date) => vec![Duration],
+            (Math::Plus | Math::Minus | Math::Modulo, Duration, Duration) => vec![Duration],
+            (Math::Plus | Math::Minus | Math::Modulo, Filesize, Filesize) => vec![Filesize],
+            (Math::Multiply | Math::Divide, Duration, b) if numeric(b) => vec![Duration],
+            (Math::Multiply | Math::Divide, Filesize, b) if numeric(b) => vec![Filesize],
+            (Math::Multiply, a, Duration) if numeric(a) => vec![Duration],
+            (Math::Multiply, a, Filesize) if numeric(a) => vec![Filesize],
+            _ => vec![Any],
+        },
+        Operator::Assignment(_) => vec![Any],
+    }
+}
+
+/// Find the output types of a call from the signatures that accept its input. An empty register
+/// is passed to the call as no input.
+fn call_output(signatures: &Signatures, decl_id: DeclId, input: &TypeSet) -> TypeSet {
+    let Some(signature) = signatures.get(&decl_id) else {
+        return TypeSet::any();
+    };
+    let input = if input.is_empty() {
+        TypeSet::of(Type::Nothing)
+    } else {
+        input.clone()
+    };
+    let mut output = TypeSet::empty();
+    for ty in input.types() {
+        let mut matched = signature
+            .iter()
+            .filter(|(accepted, _)| *ty != Type::Any && ty.is_subtype(accepted))
+            .peekable();
+        if matched.peek().is_none() {
+            // Any of the signatures could apply
+            for (_, out) in signature {
+                output = output.with(out.clone());
+            }
+        } else {
+            for (_, out) in matched {
+                output = output.with(out.clone());
+            }
+        }
+    }
+    if output.is_empty() {
+        TypeSet::any()
+    } else {
+        output
+    }
+}
+
+/// Parse a type as it's shown in a signature, like `list<string>`. Field names of records and
+/// tables aren't kept, and anything unrecognized is `any`.
+pub fn parse_type(shape: &str) -> Type {
+    let shape = shape.trim();
+    if let Some(item) = shape
+        .strip_prefix("list<")
+        .and_then(|rest| rest.strip_suffix('>'))
+    {
+        return Type::list(parse_type(item));
+    }
+    match shape {
+        "binary" => Type::Binary,
+        "block" => Type::Block,
+        "bool" => Type::Bool,
+        "cell-path" => Type::CellPath,
+        "datetime" | "date" => Type::Date,
+        "duration" => Type::Duration,
+        "error" => Type::Error,
+        "filesize" => Type::Filesize,
+        "float" => Type::Float,
+        "glob" => Type::Glob,
+        "int" => Type::Int,
+        "list" => Type::list(Type::Any),
+        "nothing" => Type::Nothing,
+        "number" => Type::Number,
+        "range" => Type::Range,
+        "string" => Type::String,
+        _ if shape.starts_with("closure") => Type::Closure,
+        _ if shape.starts_with("record") => Type::record(),
+        _ if shape.starts_with("table") => Type::table(),
+        _ => Type::Any,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::ir::DataSlice;
+
+    use super::*;
+    use crate::analysis::test_util::{ir_block, ir_block_with_data};
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    /// `if $in { $x = then } else { $x = otherwise }; $x`, not storing on a branch if its literal
+    /// is `None`. The load of `$x` is the last instruction before the return.
+    fn store_on_branches(then: Option<Literal>, otherwise: Option<Literal>) -> Vec<Instruction> {
+        let var_id = VarId::new(100);
+        let store = |lit: Option<Literal>| match lit {
+            Some(lit) => vec![
+                Instruction::LoadLiteral { dst: reg(1), lit },
+                Instruction::StoreVariable {
+                    var_id,
+                    src: reg(1),
+                },
+            ],
+            // The same length as a store, so that the path that reaches the load first depends
+            // on which branch stores
+            None => vec![
+                Instruction::LoadLiteral {
+                    dst: reg(1),
+                    lit: Literal::Nothing,
+                },
+                Instruction::Drop { src: reg(1) },
+            ],
+        };
+        let otherwise = store(otherwise);
+        let then = store(then);
+        let mut instructions = vec![Instruction::BranchIf {
+            cond: reg(0),
+            index: otherwise.len() + 2,
+        }];
+        instructions.extend(otherwise);
+        instructions.push(Instruction::Jump {
+            index: instructions.len() + then.len() + 1,
+        });
+        instructions.extend(then);
+        instructions.push(Instruction::LoadVariable {
+            dst: reg(2),
+            var_id,
+        });
+        instructions.push(Instruction::Return { src: reg(2) });
+        instructions
+    }
+
+    fn loaded_types(instructions: Vec<Instruction>) -> TypeSet {
+        let load = instructions.len() - 2;
+        let types = infer_types(&ir_block(instructions), &Signatures::new());
+        types.output[load]
+            .clone()
+            .expect("load writes a register")
+            .1
+    }
+
+    #[test]
+    fn unions_variable_types_stored_on_both_paths() {
+        let types = loaded_types(store_on_branches(
+            Some(Literal::Int(1)),
+            Some(Literal::Bool(true)),
+        ));
+        assert_eq!(types.types().len(), 2);
+        assert!(types.types().contains(&Type::Int));
+        assert!(types.types().contains(&Type::Bool));
+    }
+
+    #[test]
+    fn widens_variable_stored_on_one_path() {
+        assert!(loaded_types(store_on_branches(Some(Literal::Int(1)), None)).is_any());
+        assert!(loaded_types(store_on_branches(None, Some(Literal::Int(1)))).is_any());
+    }
+
+    #[test]
+    fn unions_register_types_at_merge() {
+        let ir_block = ir_block(vec![
+            Instruction::BranchIf {
+                cond: reg(0),
+                index: 3,
+            },
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(1),
+            },
+            Instruction::Jump { index: 4 },
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Nothing,
+            },
+            Instruction::Return { src: reg(1) },
+        ]);
+        let types = infer_types(&ir_block, &Signatures::new());
+        let entry = types.entry[4].as_ref().expect("return is reachable");
+        let returned = entry.register(reg(1));
+        assert_eq!(returned.types().len(), 2);
+        assert!(returned.types().contains(&Type::Int));
+        assert!(returned.types().contains(&Type::Nothing));
+    }
+
+    #[test]
+    fn widens_too_many_alternatives() {
+        let types = [Type::Int, Type::Bool, Type::String, Type::Float]
+            .into_iter()
+            .fold(TypeSet::empty(), TypeSet::with);
+        assert_eq!(types.types().len(), MAX_TYPES);
+        assert!(types.with(Type::Binary).is_any());
+    }
+
+    /// `{} | insert <key> 1`, with the key read from `data`
+    fn record_insert(key: DataSlice, data: &[u8]) -> TypeSet {
+        let ir_block = ir_block_with_data(
+            vec![
+                Instruction::LoadLiteral {
+                    dst: reg(1),
+                    lit: Literal::Record { capacity: 1 },
+                },
+                Instruction::LoadLiteral {
+                    dst: reg(2),
+                    lit: Literal::String(key),
+                },
+                Instruction::LoadLiteral {
+                    dst: reg(3),
+                    lit: Literal::Int(1),
+                },
+                Instruction::RecordInsert {
+                    src_dst: reg(1),
+                    key: reg(2),
+                    val: reg(3),
+                },
+                Instruction::Return { src: reg(1) },
+            ],
+            data,
+        );
+        let types = infer_types(&ir_block, &Signatures::new());
+        types.output[3].clone().expect("insert writes a register").1
+    }
+
+    #[test]
+    fn follows_record_keys() {
+        assert_eq!(
+            record_insert(DataSlice { start: 0, len: 1 }, b"a"),
+            TypeSet::of(Type::Record([("a".into(), Type::Int)].into()))
+        );
+    }
+
+    #[test]
+    fn ignores_key_outside_of_data() {
+        assert_eq!(
+            record_insert(DataSlice { start: 4, len: 1 }, b"a"),
+            TypeSet::of(Type::record())
+        );
+    }
+
+    #[test]
+    fn parses_signature_types() {
+        assert_eq!(parse_type("int"), Type::Int);
+        assert_eq!(parse_type("list<string>"), Type::list(Type::String));
+    }
+}
@@ -0,0 +1,711 @@
+use std::fmt;
+
+use nu_protocol::ir::{DataSlice, Instruction, IrBlock, Literal, RedirectMode};
+
+use super::{handlers_after, registers, Cfg, EdgeKind, ErrorHandler};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => f.write_str("error"),
+            Severity::Warning => f.write_str("warning"),
+        }
+    }
+}
+
+/// A problem found in a block, at a specific instruction if `index` is set
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub index: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Check an [`IrBlock`] for structural problems that likely indicate a compiler bug
+pub fn verify(ir_block: &IrBlock) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut report = |index: Option<usize>, severity: Severity, message: String| {
+        diagnostics.push(Diagnostic {
+            index,
+            severity,
+            message,
+        })
+    };
+
+    let len = ir_block.instructions.len();
+    for (name, field_len) in [
+        ("spans", ir_block.spans.len()),
+        ("comments", ir_block.comments.len()),
+        ("ast", ir_block.ast.len()),
+    ] {
+        if field_len != len {
+            report(
+                None,
+                Severity::Error,
+                format!("`{name}` has {field_len} entries, but there are {len} instructions"),
+            );
+        }
+    }
+
+    for (index, instruction) in ir_block.instructions.iter().enumerate() {
+        for message in check_operands(ir_block, instruction) {
+            report(Some(index), Severity::Error, message);
+        }
+        if let Some(target) = instruction.branch_target().filter(|target| *target >= len) {
+            report(
+                Some(index),
+                Severity::Error,
+                format!("branch target {target} is out of range"),
+            );
+        }
+    }
+
+    let cfg = Cfg::new(&ir_block.instructions);
+    let reachable = cfg.reachable();
+
+    for (index, instruction) in ir_block.instructions.iter().enumerate() {
+        let falls_off = super::successors(instruction, index)
+            .iter()
+            .any(|edge| edge.kind == EdgeKind::Next && edge.target == len);
+        if reachable[index] && falls_off {
+            report(
+                Some(index),
+                Severity::Error,
+                "execution can continue past the end of the block".into(),
+            );
+        }
+    }
+
+    check_registers_written(ir_block, &cfg, &mut report);
+    check_error_handlers(ir_block, &cfg, &mut report);
+    check_call_state(ir_block, &cfg, &mut report);
+
+    diagnostics.sort_by_key(|diagnostic| diagnostic.index);
+    diagnostics
+}
+
+/// Check that registers, data slices and file numbers referenced by an instruction are in range
+fn check_operands(ir_block: &IrBlock, instruction: &Instruction) -> Vec<String> {
+    let mut messages = vec![];
+
+    for reg_id in registers::uses(instruction)
+        .into_iter()
+        .chain(registers::defs(instruction))
+    {
+        if reg_id.get() >= ir_block.register_count {
+            messages.push(format!(
+                "register {reg_id} is out of range (register count is {})",
+                ir_block.register_count
+            ));
+        }
+    }
+
+    for slice in data_slices(instruction) {
+        if super::data_slice(ir_block, slice).is_none() {
+            messages.push(format!(
+                "data slice {}..{} is out of range (data is {} bytes)",
+                slice.start,
+                slice.start + slice.len,
+                ir_block.data.len()
+            ));
+        }
+    }
+
+    if let Some(file_num) = file_num(instruction).filter(|num| *num >= ir_block.file_count) {
+        messages.push(format!(
+            "file {file_num} is out of range (file count is {})",
+            ir_block.file_count
+        ));
+    }
+
+    messages
+}
+
+fn data_slices(instruction: &Instruction) -> Vec<DataSlice> {
+    match instruction {
+        Instruction::LoadLiteral {
+            lit:
+                Literal::Binary(slice)
+                | Literal::Filepath { val: slice, .. }
+                | Literal::Directory { val: slice, .. }
+                | Literal::GlobPattern { val: slice, .. }
+                | Literal::String(slice)
+                | Literal::RawString(slice),
+            ..
+        } => vec![*slice],
+        Instruction::LoadEnv { key, .. }
+        | Instruction::LoadEnvOpt { key, .. }
+        | Instruction::StoreEnv { key, .. } => vec![*key],
+        Instruction::PushFlag { name }
+        | Instruction::PushNamed { name, .. }
+        | Instruction::PushParserInfo { name, .. } => vec![*name],
+        Instruction::PushShortFlag { short } | Instruction::PushShortNamed { short, .. } => {
+            vec![*short]
+        }
+        _ => vec![],
+    }
+}
+
+fn file_num(instruction: &Instruction) -> Option<u32> {
+    match *instruction {
+        Instruction::OpenFile { file_num, .. }
+        | Instruction::WriteFile { file_num, .. }
+        | Instruction::CloseFile { file_num }
+        | Instruction::RedirectOut {
+            mode: RedirectMode::File { file_num },
+        }
+        | Instruction::RedirectErr {
+            mode: RedirectMode::File { file_num },
+        } => Some(file_num),
+        _ => None,
+    }
+}
+
+/// Report registers that might be read before anything was written to them. Reads that are
+/// expected to sometimes see `Empty` are not reported.
+fn check_registers_written(
+    ir_block: &IrBlock,
+    cfg: &Cfg,
+    report: &mut impl FnMut(Option<usize>, Severity, String),
+) {
+    let register_count = ir_block.register_count as usize;
+    let mut entry = vec![false; register_count];
+    // %0 holds the block's input
+    if let Some(input) = entry.first_mut() {
+        *input = true;
+    }
+
+    let states = cfg.forward(
+        entry,
+        |index, written, edge| {
+            let mut written = written.clone();
+            if let Some(reg_id) = registers::def(&ir_block.instructions[index], edge.kind) {
+                if let Some(slot) = written.get_mut(reg_id.get() as usize) {
+                    *slot = true;
+                }
+            }
+            written
+        },
+        |_, a, b| a.iter().zip(b).map(|(a, b)| *a && *b).collect(),
+    );
+
+    for (index, instruction) in ir_block.instructions.iter().enumerate() {
+        let Some(written) = &states[index] else {
+            continue;
+        };
+        if registers::accepts_empty(instruction) {
+            continue;
+        }
+        for reg_id in registers::uses(instruction) {
+            if written.get(reg_id.get() as usize) == Some(&false) {
+                report(
+                    Some(index),
+                    Severity::Warning,
+                    format!("{reg_id} may be read before it is written"),
+                );
+            }
+        }
+    }
+}
+
+/// Walk the control flow graph once, giving each instruction the state it was first reached with,
+/// and calling `conflict` for any other path that reaches it with a different state
+fn propagate<S: Clone + PartialEq>(
+    cfg: &Cfg,
+    entry: S,
+    mut transfer: impl FnMut(usize, &S, EdgeKind) -> S,
+    mut conflict: impl FnMut(usize, &S, &S),
+) -> Vec<Option<S>> {
+    let mut states: Vec<Option<S>> = vec![None; cfg.len()];
+    let mut stack = vec![];
+    if cfg.len() > 0 {
+        states[0] = Some(entry);
+        stack.push(0);
+    }
+    while let Some(index) = stack.pop() {
+        let state = states[index].clone().expect("state was set before pushing");
+        for edge in &cfg.successors[index] {
+            let out = transfer(index, &state, edge.kind);
+            match &states[edge.target] {
+                Some(existing) if *existing != out => conflict(edge.target, existing, &out),
+                Some(_) => (),
+                None => {
+                    states[edge.target] = Some(out);
+                    stack.push(edge.target);
+                }
+            }
+        }
+    }
+    states
+}
+
+/// Check that `OnError`/`OnErrorInto` and `PopErrorHandler` are balanced on every path
+fn check_error_handlers(
+    ir_block: &IrBlock,
+    cfg: &Cfg,
+    report: &mut impl FnMut(Option<usize>, Severity, String),
+) {
+    let mut conflicts = vec![];
+    let states = propagate(
+        cfg,
+        Vec::<ErrorHandler>::new(),
+        |index, handlers, kind| {
+            handlers_after(&ir_block.instructions[index], index, handlers, kind)
+        },
+        |index, a: &Vec<ErrorHandler>, b: &Vec<ErrorHandler>| {
+            conflicts.push((
+                index,
+                format!(
+                    "error handlers differ between paths: {:?} vs {:?}",
+                    targets(a),
+                    targets(b)
+                ),
+            ))
+        },
+    );
+
+    for (index, message) in conflicts {
+        report(Some(index), Severity::Error, message);
+    }
+
+    for (index, instruction) in ir_block.instructions.iter().enumerate() {
+        let Some(handlers) = &states[index] else {
+            continue;
+        };
+        match instruction {
+            Instruction::PopErrorHandler if handlers.is_empty() => report(
+                Some(index),
+                Severity::Error,
+                "no error handler to pop".into(),
+            ),
+            Instruction::Return { .. } if !handlers.is_empty() => report(
+                Some(index),
+                Severity::Warning,
+                format!(
+                    "returns with error handlers still pushed: {:?}",
+                    targets(handlers)
+                ),
+            ),
+            _ => (),
+        }
+    }
+}
+
+fn targets(handlers: &[ErrorHandler]) -> Vec<usize> {
+    handlers.iter().map(|handler| handler.target).collect()
+}
+
+/// State that is kept between instructions for the next call
+#[derive(Clone, Default)]
+struct CallState {
+    /// Index of the first instruction that pushed an argument that hasn't been used by a call
+    args: Option<usize>,
+    redirect_out: Option<usize>,
+    redirect_err: Option<usize>,
+    open_files: Vec<u32>,
+}
+
+impl PartialEq for CallState {
+    /// Redirections with the `caller` mode are left pending on purpose before returning, so only
+    /// arguments and files have to match between paths
+    fn eq(&self, other: &Self) -> bool {
+        self.args.is_some() == other.args.is_some() && self.open_files == other.open_files
+    }
+}
+
+/// Check that arguments and redirections are used by a call before anything else happens to them,
+/// and that files are opened and closed properly
+fn check_call_state(
+    ir_block: &IrBlock,
+    cfg: &Cfg,
+    report: &mut impl FnMut(Option<usize>, Severity, String),
+) {
+    let mut conflicts = vec![];
+    let states = propagate(
+        cfg,
+        CallState::default(),
+        |index, state, kind| call_state_after(ir_block, index, state, kind),
+        |index, _, _| {
+            conflicts.push((
+                index,
+                "pending arguments or open files differ between paths".to_string(),
+            ))
+        },
+    );
+
+    for (index, message) in conflicts {
+        report(Some(index), Severity::Warning, message);
+    }
+
+    for (index, instruction) in ir_block.instructions.iter().enumerate() {
+        let Some(state) = &states[index] else {
+            continue;
+        };
+        match instruction {
+            Instruction::RedirectOut { .. } => {
+                if let Some(previous) = state.redirect_out {
+                    report(
+                        Some(index),
+                        Severity::Warning,
+                        format!("replaces the stdout redirection from {previous} before a call"),
+                    );
+                }
+            }
+            Instruction::RedirectErr { .. } => {
+                if let Some(previous) = state.redirect_err {
+                    report(
+                        Some(index),
+                        Severity::Warning,
+                        format!("replaces the stderr redirection from {previous} before a call"),
+                    );
+                }
+            }
+            Instruction::OpenFile { file_num, .. } if state.open_files.contains(file_num) => {
+                report(
+                    Some(index),
+                    Severity::Error,
+                    format!("file {file_num} is already open"),
+                );
+            }
+            Instruction::WriteFile { file_num, .. } | Instruction::CloseFile { file_num }
+                if !state.open_files.contains(file_num) =>
+            {
+                report(
+                    Some(index),
+                    Severity::Error,
+                    format!("file {file_num} is not open"),
+                );
+            }
+            Instruction::Return { .. } | Instruction::ReturnEarly { .. } => {
+                check_exit_state(ir_block, index, state, report);
+            }
+            // Falling off the end is an error already, but check what it leaves pending too
+            _ if super::successors(instruction, index).iter().any(|edge| {
+                edge.kind == EdgeKind::Next && edge.target == ir_block.instructions.len()
+            }) =>
+            {
+                let state = call_state_after(ir_block, index, state, EdgeKind::Next);
+                check_exit_state(ir_block, index, &state, report);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Update the call state along an edge out of an instruction
+fn call_state_after(
+    ir_block: &IrBlock,
+    index: usize,
+    state: &CallState,
+    kind: EdgeKind,
+) -> CallState {
+    let mut state = state.clone();
+    if kind == EdgeKind::Error {
+        // Errors usually come from calls, which reset this state
+        state.args = None;
+        state.redirect_out = None;
+        state.redirect_err = None;
+        return state;
+    }
+    match &ir_block.instructions[index] {
+        Instruction::PushPositional { .. }
+        | Instruction::AppendRest { .. }
+        | Instruction::PushFlag { .. }
+        | Instruction::PushShortFlag { .. }
+        | Instruction::PushNamed { .. }
+        | Instruction::PushShortNamed { .. }
+        | Instruction::PushParserInfo { .. } => {
+            state.args.get_or_insert(index);
+        }
+        Instruction::RedirectOut { .. } => state.redirect_out = Some(index),
+        Instruction::RedirectErr { .. } => state.redirect_err = Some(index),
+        Instruction::Call { .. } => {
+            state.args = None;
+            state.redirect_out = None;
+            state.redirect_err = None;
+        }
+        Instruction::OpenFile { file_num, .. } => state.open_files.push(*file_num),
+        Instruction::CloseFile { file_num } => state.open_files.retain(|n| n != file_num),
+        _ => (),
+    }
+    state
+}
+
+/// Report arguments, redirections and files that are left pending when the block exits at `index`
+fn check_exit_state(
+    ir_block: &IrBlock,
+    index: usize,
+    state: &CallState,
+    report: &mut impl FnMut(Option<usize>, Severity, String),
+) {
+    if let Some(args) = state.args {
+        report(
+            Some(index),
+            Severity::Warning,
+            format!("returns with unused arguments pushed from {args}"),
+        );
+    }
+    // Redirections to the caller are left for the caller's next call on purpose
+    let pending = |redirect: Option<usize>| {
+        redirect.filter(|&at| {
+            !matches!(
+                ir_block.instructions[at],
+                Instruction::RedirectOut {
+                    mode: RedirectMode::Caller
+                } | Instruction::RedirectErr {
+                    mode: RedirectMode::Caller
+                }
+            )
+        })
+    };
+    for (stream, redirect) in [
+        ("stdout", state.redirect_out),
+        ("stderr", state.redirect_err),
+    ] {
+        if let Some(redirect) = pending(redirect) {
+            report(
+                Some(index),
+                Severity::Warning,
+                format!("returns with the {stream} redirection from {redirect} unused"),
+            );
+        }
+    }
+    if !state.open_files.is_empty() {
+        report(
+            Some(index),
+            Severity::Warning,
+            format!("returns with files still open: {:?}", state.open_files),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{ir::Instruction, ir::Literal, RegId};
+
+    use super::*;
+    use crate::analysis::test_util::{ir_block, ir_block_with_data};
+
+    fn reg(n: u32) -> RegId {
+        RegId::new(n)
+    }
+
+    fn messages(ir_block: &IrBlock) -> Vec<(Option<usize>, String)> {
+        verify(ir_block)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.index, diagnostic.message))
+            .collect()
+    }
+
+    #[test]
+    fn accepts_well_formed_block() {
+        let ir_block = ir_block(vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(1),
+            },
+            Instruction::Return { src: reg(1) },
+        ]);
+        assert_eq!(messages(&ir_block), vec![]);
+    }
+
+    #[test]
+    fn reports_branch_target_out_of_range() {
+        let ir_block = ir_block(vec![Instruction::Jump { index: 5 }]);
+        assert_eq!(
+            messages(&ir_block),
+            vec![(Some(0), "branch target 5 is out of range".into())]
+        );
+    }
+
+    #[test]
+    fn reports_register_out_of_range() {
+        let mut ir_block = ir_block(vec![Instruction::Return { src: reg(2) }]);
+        ir_block.register_count = 1;
+        assert!(messages(&ir_block).contains(&(
+            Some(0),
+            "register %2 is out of range (register count is 1)".into()
+        )));
+    }
+
+    #[test]
+    fn reports_data_slice_out_of_range() {
+        let ir_block = ir_block_with_data(
+            vec![
+                Instruction::LoadLiteral {
+                    dst: reg(1),
+                    lit: Literal::String(DataSlice { start: 2, len: 4 }),
+                },
+                Instruction::Return { src: reg(1) },
+            ],
+            b"abc",
+        );
+        assert_eq!(
+            messages(&ir_block),
+            vec![(
+                Some(0),
+                "data slice 2..6 is out of range (data is 3 bytes)".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_field_length_mismatch() {
+        let mut ir_block = ir_block(vec![Instruction::Return { src: reg(0) }]);
+        ir_block.spans.clear();
+        assert_eq!(
+            messages(&ir_block),
+            vec![(
+                None,
+                "`spans` has 0 entries, but there are 1 instructions".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_falling_off_the_end() {
+        let ir_block = ir_block(vec![Instruction::LoadLiteral {
+            dst: reg(1),
+            lit: Literal::Nothing,
+        }]);
+        assert_eq!(
+            messages(&ir_block),
+            vec![(
+                Some(0),
+                "execution can continue past the end of the block".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_read_before_write_on_one_path() {
+        let ir_block = ir_block(vec![
+            Instruction::BranchIf {
+                cond: reg(0),
+                index: 2,
+            },
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Bool(true),
+            },
+            Instruction::Not { src_dst: reg(1) },
+            Instruction::Return { src: reg(1) },
+        ]);
+        assert_eq!(
+            messages(&ir_block),
+            vec![(Some(2), "%1 may be read before it is written".into())]
+        );
+    }
+
+    #[test]
+    fn reports_unbalanced_error_handlers() {
+        let ir_block = ir_block(vec![
+            Instruction::PopErrorHandler,
+            Instruction::OnError { index: 3 },
+            Instruction::Return { src: reg(0) },
+            Instruction::Return { src: reg(0) },
+        ]);
+        assert_eq!(
+            messages(&ir_block),
+            vec![
+                (Some(0), "no error handler to pop".into()),
+                (
+                    Some(2),
+                    "returns with error handlers still pushed: [3]".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_arguments_left_at_return() {
+        let ir_block = ir_block(vec![
+            Instruction::LoadLiteral {
+                dst: reg(1),
+                lit: Literal::Int(1),
+            },
+            Instruction::PushPositional { src: reg(1) },
+            Instruction::Return { src: reg(0) },
+        ]);
+        assert_eq!(
+            messages(&ir_block),
+            vec![(
+                Some(2),
+                "returns with unused arguments pushed from 1".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_redirection_left_at_return() {
+        let ir_block = ir_block(vec![
+            Instruction::RedirectOut {
+                mode: RedirectMode::Null,
+            },
+            Instruction::Return { src: reg(0) },
+        ]);
+        assert_eq!(
+            messages(&ir_block),
+            vec![(
+                Some(1),
+                "returns with the stdout redirection from 0 unused".into()
+            )]
+        );
+    }
+
+    #[test]
+    fn allows_caller_redirection_at_return() {
+        let ir_block = ir_block(vec![
+            Instruction::RedirectErr {
+                mode: RedirectMode::Caller,
+            },
+            Instruction::Return { src: reg(0) },
+        ]);
+        assert_eq!(messages(&ir_block), vec![]);
+    }
+
+    #[test]
+    fn reports_replaced_redirection() {
+        let ir_block = ir_block(vec![
+            Instruction::RedirectOut {
+                mode: RedirectMode::Pipe,
+            },
+            Instruction::RedirectOut {
+                mode: RedirectMode::Null,
+            },
+            Instruction::Return { src: reg(0) },
+        ]);
+        assert_eq!(
+            messages(&ir_block),
+            vec![
+                (
+                    Some(1),
+                    "replaces the stdout redirection from 0 before a call".into()
+                ),
+                (
+                    Some(2),
+                    "returns with the stdout redirection from 1 unused".into()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_files_not_open() {
+        let mut ir_block = ir_block(vec![
+            Instruction::CloseFile { file_num: 0 },
+            Instruction::Return { src: reg(0) },
+        ]);
+        ir_block.file_count = 1;
+        assert_eq!(
+            messages(&ir_block),
+            vec![(Some(0), "file 0 is not open".into())]
+        );
+    }
+}
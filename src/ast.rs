@@ -0,0 +1,248 @@
+use std::ops::Range;
+
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{IntoSpanned, LabeledError, PipelineData, Span, Value};
+use serde_json::Value as Json;
+
+/// A node in a simplified tree of the parsed AST of a block
+pub struct AstNode {
+    pub label: String,
+    /// The range of the block's source code that this node was parsed from
+    pub span: Option<Range<usize>>,
+    pub children: Vec<AstNode>,
+}
+
+/// Parse the source of a block with `ast`, including any blocks nested inside of it
+pub fn parse(
+    engine: &EngineInterface,
+    source: &str,
+    head: Span,
+) -> Result<Vec<AstNode>, LabeledError> {
+    Parser {
+        engine,
+        source,
+        head,
+        base: 0,
+        offset: 0,
+    }
+    .block(0..source.len())
+}
+
+/// Find the path of child indices to the most specific node that covers `span`
+pub fn find_path(nodes: &[AstNode], span: &Range<usize>) -> Vec<usize> {
+    fn search(nodes: &[AstNode], span: &Range<usize>) -> Option<(Vec<usize>, usize)> {
+        let mut best: Option<(Vec<usize>, usize)> = None;
+        for (index, node) in nodes.iter().enumerate() {
+            let covering = node
+                .span
+                .as_ref()
+                .filter(|node_span| node_span.start <= span.start && span.end <= node_span.end);
+            // Nodes without a span (like lists) might still have children that cover it
+            if covering.is_none() && node.span.is_some() {
+                continue;
+            }
+            let candidate = search(&node.children, span)
+                .map(|(mut path, len)| {
+                    path.insert(0, index);
+                    (path, len)
+                })
+                .or_else(|| covering.map(|node_span| (vec![index], node_span.len())));
+            if let Some(candidate) = candidate {
+                // Prefer the smallest span, and then the deepest node
+                if best.as_ref().map_or(true, |(path, len)| {
+                    candidate.1 < *len || (candidate.1 == *len && candidate.0.len() > path.len())
+                }) {
+                    best = Some(candidate);
+                }
+            }
+        }
+        best
+    }
+    search(nodes, span)
+        .map(|(path, _)| path)
+        .unwrap_or_default()
+}
+
+struct Parser<'a> {
+    engine: &'a EngineInterface,
+    source: &'a str,
+    head: Span,
+    /// The start of the span that `ast` parsed the current block at
+    base: usize,
+    /// The offset into `source` of the current block
+    offset: usize,
+}
+
+impl Parser<'_> {
+    fn ast_json(&self, contents: &str) -> Result<Json, LabeledError> {
+        let head = self.head;
+        let Some(decl_id) = self.engine.find_decl("ast")? else {
+            return Err(LabeledError::new("Can't find `ast`")
+                .with_label("must be in scope to show the AST", head));
+        };
+
+        let result = self.engine.call_decl(
+            decl_id,
+            EvaluatedCall::new(head)
+                .with_named("json".into_spanned(head), Value::bool(true, head))
+                .with_named("minify".into_spanned(head), Value::bool(true, head))
+                .with_positional(Value::string(contents, head)),
+            PipelineData::Empty,
+            true,
+            false,
+        )?;
+
+        let json = result.into_value(head)?.into_string()?;
+
+        serde_json::from_str(&json).map_err(|err| {
+            LabeledError::new("Failed to parse output of `ast`").with_label(err.to_string(), head)
+        })
+    }
+
+    /// Parse the contents of the block at `range` within the source, without its delimiters
+    fn block(&mut self, range: Range<usize>) -> Result<Vec<AstNode>, LabeledError> {
+        let range = block_contents(self.source, range);
+        let Some(contents) = self.source.get(range.clone()) else {
+            return Err(LabeledError::new("Can't parse the block")
+                .with_label("its span is outside of the source code", self.head));
+        };
+        let mut output = self.ast_json(contents)?;
+
+        // The block might also be returned as a JSON string within the JSON
+        let mut block = output["block"].take();
+        if let Json::String(string) = &block {
+            block = serde_json::from_str(string).map_err(|err| {
+                LabeledError::new("Failed to parse output of `ast`")
+                    .with_label(err.to_string(), self.head)
+            })?;
+        }
+
+        let (saved_base, saved_offset) = (self.base, self.offset);
+        self.base = block["span"]["start"].as_u64().unwrap_or(0) as usize;
+        self.offset = range.start;
+
+        let nodes = block["pipelines"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|pipeline| self.node("Pipeline".into(), pipeline).transpose())
+            .collect();
+
+        (self.base, self.offset) = (saved_base, saved_offset);
+        nodes
+    }
+
+    /// Convert a span from `ast` to a range within the source
+    fn span(&self, span: &Json) -> Option<Range<usize>> {
+        let start = (span["start"].as_u64()? as usize).checked_sub(self.base)? + self.offset;
+        let end = (span["end"].as_u64()? as usize).checked_sub(self.base)? + self.offset;
+        let range = start..end.min(self.source.len());
+        // Skip spans that don't fit in the source, so they can't be sliced out of it
+        self.source.get(range.clone()).map(|_| range)
+    }
+
+    fn node(&mut self, label: String, value: &Json) -> Result<Option<AstNode>, LabeledError> {
+        let mut node = AstNode {
+            label,
+            span: None,
+            children: vec![],
+        };
+
+        match value {
+            Json::Null => return Ok(None),
+            Json::Object(map) if map.is_empty() => return Ok(None),
+            Json::Array(items) if items.is_empty() => return Ok(None),
+            // Spans are attached to the nodes they belong to instead
+            Json::Object(map) if map.len() == 2 && map.contains_key("start") => return Ok(None),
+            // An expression is labelled with its kind and type
+            Json::Object(map) if map.contains_key("expr") && map.contains_key("ty") => {
+                node.span = map.get("span").and_then(|span| self.span(span));
+                self.variant(&mut node, &map["expr"])?;
+                if let Some(ty) = map["ty"].as_str() {
+                    node.label.push_str(&format!(" ({})", ty.to_lowercase()));
+                }
+            }
+            // Enums are serialized as {"Variant": contents}
+            Json::Object(map)
+                if map.len() == 1 && map.keys().all(|key| key.starts_with(char::is_uppercase)) =>
+            {
+                self.variant(&mut node, value)?;
+            }
+            _ => {
+                node.span = value.get("span").and_then(|span| self.span(span));
+                self.contents(&mut node, value)?;
+            }
+        }
+
+        Ok(Some(node))
+    }
+
+    fn variant(&mut self, node: &mut AstNode, value: &Json) -> Result<(), LabeledError> {
+        match value {
+            Json::String(variant) => {
+                node.label = format!("{} {variant}", node.label).trim_start().into();
+            }
+            Json::Object(map) => {
+                for (variant, contents) in map {
+                    node.label = format!("{} {variant}", node.label).trim_start().into();
+                    match (variant.as_str(), &node.span) {
+                        // Nested blocks are only referenced by ID, so parse them separately
+                        ("Block" | "Closure" | "RowCondition" | "Subexpression", Some(span)) => {
+                            match self.block(span.clone()) {
+                                Ok(children) => node.children = children,
+                                Err(_) => self.contents(node, contents)?,
+                            }
+                        }
+                        _ => self.contents(node, contents)?,
+                    }
+                }
+            }
+            _ => self.contents(node, value)?,
+        }
+        Ok(())
+    }
+
+    fn contents(&mut self, node: &mut AstNode, value: &Json) -> Result<(), LabeledError> {
+        match value {
+            Json::Object(map) if map.contains_key("expr") && map.contains_key("ty") => {
+                node.children.extend(self.node(String::new(), value)?);
+            }
+            Json::Object(map) => {
+                for (key, value) in map {
+                    if key != "span" && key != "span_id" {
+                        node.children.extend(self.node(format!("{key}:"), value)?);
+                    }
+                }
+            }
+            Json::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    node.children.extend(self.node(format!("[{index}]"), item)?);
+                }
+            }
+            Json::String(string) => node.label.push_str(&format!(" {string:?}")),
+            Json::Null => (),
+            other => node.label.push_str(&format!(" {other}")),
+        }
+        Ok(())
+    }
+}
+
+/// Strip the delimiters of a block, closure or subexpression (and closure parameters) so that its
+/// contents can be parsed on their own
+fn block_contents(source: &str, range: Range<usize>) -> Range<usize> {
+    let Some(text) = source.get(range.clone()) else {
+        return range;
+    };
+    let inner = match text.as_bytes() {
+        [b'{', .., b'}'] | [b'(', .., b')'] => &text[1..text.len() - 1],
+        _ => return range,
+    };
+    let mut start = range.start + 1;
+    let trimmed = inner.trim_start();
+    if let Some(params) = trimmed.strip_prefix('|') {
+        if let Some(end) = params.find('|') {
+            start += inner.len() - params.len() + end + 1;
+        }
+    }
+    start..range.end - 1
+}
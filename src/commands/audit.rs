@@ -0,0 +1,179 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{
+    ir::{Instruction, RedirectMode},
+    record, Category, Example, LabeledError, Signature, Type, Value,
+};
+
+use crate::{
+    analysis::{self, EnvAccessKind},
+    data,
+    pseudocode::Pseudocode,
+    ExploreIrPlugin,
+};
+
+use super::{get_target, span_value, with_target};
+
+/// Categories of commands that touch something outside of the script
+const EFFECT_CATEGORIES: &[&str] = &["filesystem", "network", "system", "platform", "env"];
+
+/// Commands that run other programs
+const EXTERNAL_COMMANDS: &[&str] = &["run-external", "exec"];
+
+pub struct IrAudit;
+
+impl SimplePluginCommand for IrAudit {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir audit"
+    }
+
+    fn signature(&self) -> Signature {
+        with_target(Signature::build(PluginCommand::name(self)))
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "List the side effects of a block or definition, and of every block and custom command it can reach."
+    }
+
+    fn extra_description(&self) -> &str {
+        "The `kind` of each side effect is one of:
+  external   - runs another program, with `run-external` or `exec`
+  filesystem, network, system, platform, env
+             - calls a command in that category
+  open-file  - opens a file for a redirection
+  write-file - writes to a file opened for a redirection
+  redirect   - redirects the output of a call to a file
+  store-env  - sets an environment variable
+  read-env   - reads an environment variable, or all of `$env`
+
+Custom commands are followed into their own IR, so their side effects are listed where they \
+happen, with the `block_id` they're in.
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir audit 'my command'",
+                description: "List everything a custom command might do to the system.",
+                result: None,
+            },
+            Example {
+                example: "ir audit 'my command' | where kind in [external network]",
+                description: "Find the programs a module runs and the network requests it makes.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let head = call.head;
+        let view_ir = get_target(engine, call)?;
+        let blocks = data::reachable_blocks(engine, view_ir, head)?;
+        let commands = data::commands(engine, head)?;
+
+        let mut rows = vec![];
+        for view_ir in &blocks {
+            let ir_block = &view_ir.ir_block;
+            let pseudocode = Pseudocode::new(view_ir);
+            let env_accesses = analysis::env_accesses(ir_block);
+            for (index, instruction) in ir_block.instructions.iter().enumerate() {
+                let (kind, detail) = if let Some(access) =
+                    env_accesses.iter().find(|access| access.index == index)
+                {
+                    let kind = match access.kind {
+                        EnvAccessKind::Write => "store-env",
+                        _ => "read-env",
+                    };
+                    let detail = match access.kind {
+                        EnvAccessKind::ReadAll => access.name.clone(),
+                        EnvAccessKind::ReadOptional => format!("$env.{}?", access.name),
+                        _ => format!("$env.{}", access.name),
+                    };
+                    (kind, detail)
+                } else {
+                    match instruction {
+                        Instruction::Call { decl_id, .. } => {
+                            let Some(command) = commands.get(decl_id) else {
+                                continue;
+                            };
+                            let kind = if EXTERNAL_COMMANDS.contains(&command.name.as_str()) {
+                                "external"
+                            } else if EFFECT_CATEGORIES.contains(&command.category.as_str()) {
+                                command.category.as_str()
+                            } else {
+                                continue;
+                            };
+                            (
+                                kind,
+                                pseudocode
+                                    .call_line(index)
+                                    .unwrap_or_else(|| command.name.clone()),
+                            )
+                        }
+                        Instruction::OpenFile { path, append, .. } => (
+                            "open-file",
+                            format!(
+                                "{}{}",
+                                pseudocode.expr(*path, index, 0),
+                                if *append { " (append)" } else { "" }
+                            ),
+                        ),
+                        Instruction::WriteFile { file_num, .. } => {
+                            ("write-file", format!("file({file_num})"))
+                        }
+                        Instruction::RedirectOut {
+                            mode: mode @ RedirectMode::File { .. },
+                        } => (
+                            "redirect",
+                            format!(
+                                "o>{}",
+                                pseudocode.redirect(*mode, index, 0).unwrap_or_default()
+                            ),
+                        ),
+                        Instruction::RedirectErr {
+                            mode: mode @ RedirectMode::File { .. },
+                        } => (
+                            "redirect",
+                            format!(
+                                "e>{}",
+                                pseudocode.redirect(*mode, index, 0).unwrap_or_default()
+                            ),
+                        ),
+                        _ => continue,
+                    }
+                };
+                rows.push(Value::record(
+                    record! {
+                        "block_id" => Value::int(view_ir.block_id.get() as i64, head),
+                        "index" => Value::int(index as i64, head),
+                        "kind" => Value::string(kind, head),
+                        "detail" => Value::string(detail, head),
+                        "instruction" => Value::string(
+                            &view_ir.formatted_instructions[index],
+                            head,
+                        ),
+                        "span" => ir_block
+                            .spans
+                            .get(index)
+                            .map(|span| span_value(*span, head))
+                            .unwrap_or(Value::nothing(head)),
+                    },
+                    head,
+                ));
+            }
+        }
+
+        Ok(Value::list(rows, head))
+    }
+}
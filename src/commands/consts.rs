@@ -0,0 +1,102 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{
+    ir::Instruction, record, Category, Example, LabeledError, Signature, Type, Value,
+};
+
+use crate::{analysis, ExploreIrPlugin};
+
+use super::{get_target, span_value, with_target};
+
+pub struct IrConsts;
+
+impl SimplePluginCommand for IrConsts {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir consts"
+    }
+
+    fn signature(&self) -> Signature {
+        with_target(Signature::build(PluginCommand::name(self)))
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "Find the instructions in the IR of a block or definition that always produce the same value."
+    }
+
+    fn extra_description(&self) -> &str {
+        "Constants are propagated from literals through moves, variables stored in the block, \
+        operators, `not` and string interpolation. Instructions that only load a literal aren't \
+        listed. `foldable` is true for operators and string appends that are computed at runtime \
+        even though their result is known at compile time, and `in_loop` is true if they're \
+        computed again on every iteration of a loop.
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir consts { let x = 2; $x * 3 }",
+                description: "Find the constants in a block.",
+                result: None,
+            },
+            Example {
+                example: "ir consts 'my command' | where foldable and in_loop",
+                description: "Find work that could be hoisted out of loops in a custom command.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let head = call.head;
+        let view_ir = get_target(engine, call)?;
+        let ir_block = &view_ir.ir_block;
+        let loops = analysis::loops(&ir_block.instructions);
+
+        let rows = analysis::constants(ir_block)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, value)| {
+                let instruction = &ir_block.instructions[index];
+                if matches!(
+                    instruction,
+                    Instruction::LoadLiteral { .. } | Instruction::LoadValue { .. }
+                ) {
+                    return None;
+                }
+                let value = value?.with_span(head);
+                let in_loop = loops.iter().any(|l| l.body.contains(&index));
+                Some(Value::record(
+                    record! {
+                        "index" => Value::int(index as i64, head),
+                        "instruction" => Value::string(
+                            &view_ir.formatted_instructions[index],
+                            head,
+                        ),
+                        "value" => value,
+                        "foldable" => Value::bool(analysis::is_foldable(instruction), head),
+                        "in_loop" => Value::bool(in_loop, head),
+                        "span" => ir_block
+                            .spans
+                            .get(index)
+                            .map(|span| span_value(*span, head))
+                            .unwrap_or(Value::nothing(head)),
+                    },
+                    head,
+                ))
+            })
+            .collect();
+
+        Ok(Value::list(rows, head))
+    }
+}
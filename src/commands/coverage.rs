@@ -0,0 +1,129 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{record, Category, Example, LabeledError, Signature, SyntaxShape, Type, Value};
+
+use crate::{data, profile, ExploreIrPlugin};
+
+use super::{get_target, span_value, with_target};
+
+pub struct IrCoverage;
+
+impl SimplePluginCommand for IrCoverage {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir coverage"
+    }
+
+    fn signature(&self) -> Signature {
+        with_target(Signature::build(PluginCommand::name(self)))
+            .required(
+                "closure",
+                SyntaxShape::Closure(None),
+                "The code to run, which should use the target.",
+            )
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "Find which instructions and branches of a block and the blocks nested in it ran while running a closure."
+    }
+
+    fn extra_description(&self) -> &str {
+        "The closure is run under `debug profile`, and there's a row for the target block and for \
+        each block, closure and row condition nested in it. `coverage` is the percentage of \
+        instructions that ran at least once, and `branches_covered` counts the ways that \
+        conditional branches went, out of two for each branch in `branches`. `uncovered` lists \
+        the instructions that never ran.
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir coverage 'my command' { my command test.csv }",
+                description: "Find the code in a custom command that isn't run by a test.",
+                result: None,
+            },
+            Example {
+                example:
+                    "ir coverage 'my command' { my command test.csv } | get uncovered | flatten",
+                description: "List every instruction that never ran.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let head = call.head;
+        let view_ir = get_target(engine, call)?;
+        let blocks = data::nested_blocks(engine, view_ir, head)?;
+        let rows = profile::run(engine, call.req(1)?, head)?;
+
+        let table = blocks
+            .iter()
+            .map(|view_ir| {
+                let ir_block = &view_ir.ir_block;
+                let profile = profile::block_profile(&rows, view_ir);
+                let branches = profile::branch_coverage(&rows, view_ir);
+                let covered = profile.iter().filter(|profile| profile.hits > 0).count();
+                let coverage = if profile.is_empty() {
+                    100.0
+                } else {
+                    covered as f64 * 100.0 / profile.len() as f64
+                };
+                let branches_covered = branches
+                    .iter()
+                    .map(|branch| branch.taken as usize + branch.not_taken as usize)
+                    .sum::<usize>();
+                let uncovered = profile
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, profile)| profile.hits == 0)
+                    .map(|(index, _)| {
+                        Value::record(
+                            record! {
+                                "index" => Value::int(index as i64, head),
+                                "instruction" => Value::string(
+                                    &view_ir.formatted_instructions[index],
+                                    head,
+                                ),
+                                "span" => ir_block
+                                    .spans
+                                    .get(index)
+                                    .map(|span| span_value(*span, head))
+                                    .unwrap_or(Value::nothing(head)),
+                            },
+                            head,
+                        )
+                    })
+                    .collect();
+                Value::record(
+                    record! {
+                        "block_id" => Value::int(view_ir.block_id.get() as i64, head),
+                        "span" => view_ir
+                            .span
+                            .map(|span| span_value(span, head))
+                            .unwrap_or(Value::nothing(head)),
+                        "instructions" => Value::int(profile.len() as i64, head),
+                        "covered" => Value::int(covered as i64, head),
+                        "coverage" => Value::float(coverage, head),
+                        "branches" => Value::int(branches.len() as i64, head),
+                        "branches_covered" => Value::int(branches_covered as i64, head),
+                        "uncovered" => Value::list(uncovered, head),
+                    },
+                    head,
+                )
+            })
+            .collect();
+
+        Ok(Value::list(table, head))
+    }
+}
@@ -0,0 +1,87 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{record, Category, Example, LabeledError, Signature, Type, Value};
+
+use crate::{pseudocode::Pseudocode, ExploreIrPlugin};
+
+use super::{get_target, with_target};
+
+pub struct IrDecompile;
+
+impl SimplePluginCommand for IrDecompile {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir decompile"
+    }
+
+    fn signature(&self) -> Signature {
+        with_target(Signature::build(PluginCommand::name(self)))
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "Reconstruct Nushell-like pseudocode from the IR of a block or definition."
+    }
+
+    fn extra_description(&self) -> &str {
+        "Recognizes the patterns that the compiler generates for `if`/`else`, `for`, `while`, \
+        `loop`, `try`/`catch` and `match`, and rebuilds calls from their arguments. Values left \
+        in registers at the end of a branch are shown as assignments to the register, and \
+        instructions that don't fit a pattern are shown as comments. Each line lists the \
+        instructions it was reconstructed from.
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir decompile { if $in > 1 { 'a' } else { 'b' } }",
+                description: "Decompile the IR of a block.",
+                result: None,
+            },
+            Example {
+                example: "ir decompile 'std bench' | get code | str join \"\\n\"",
+                description: "Show the decompiled code of the 'std bench' command.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let head = call.head;
+        let view_ir = get_target(engine, call)?;
+
+        let rows = Pseudocode::new(&view_ir)
+            .decompile()
+            .into_iter()
+            .map(|line| {
+                Value::record(
+                    record! {
+                        "code" => Value::string(
+                            format!("{}{}", "    ".repeat(line.depth), line.text),
+                            head,
+                        ),
+                        "instructions" => Value::list(
+                            line.instructions
+                                .iter()
+                                .map(|&index| Value::int(index as i64, head))
+                                .collect(),
+                            head,
+                        ),
+                    },
+                    head,
+                )
+            })
+            .collect();
+
+        Ok(Value::list(rows, head))
+    }
+}
@@ -0,0 +1,141 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{record, Category, Example, LabeledError, Signature, Type, Value};
+
+use crate::{
+    analysis::{self, EnvAccessKind},
+    data, ExploreIrPlugin,
+};
+
+use super::{get_target, span_value, with_target};
+
+pub struct IrEnv;
+
+/// How one environment variable is used across all of the blocks
+struct Usage {
+    name: String,
+    locations: Vec<Value>,
+    reads: usize,
+    writes: usize,
+    /// Every read is `$env.NAME?`
+    optional: bool,
+}
+
+impl SimplePluginCommand for IrEnv {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir env"
+    }
+
+    fn signature(&self) -> Signature {
+        with_target(Signature::build(PluginCommand::name(self)))
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "List the environment variables that a block or definition, and every block and custom command it can reach, read and write."
+    }
+
+    fn extra_description(&self) -> &str {
+        "There's a row for each variable, with the number of times it's read and written, and \
+        whether every read is optional (`$env.NAME?`), so that it's fine for it to be missing. \
+        Uses of `$env` as a whole, which could read any variable, are listed under `$env`. \
+        `locations` has the block, instruction and span of each use.
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir env 'my command'",
+                description: "Find the environment variables a custom command depends on.",
+                result: None,
+            },
+            Example {
+                example: "ir env 'my command' | where reads > 0 and not optional",
+                description: "Find the variables that must be set for a custom command to work.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let head = call.head;
+        let view_ir = get_target(engine, call)?;
+        let blocks = data::reachable_blocks(engine, view_ir, head)?;
+
+        // The uses of each variable across all of the blocks, in the order they're found
+        let mut usage: Vec<Usage> = vec![];
+        for view_ir in &blocks {
+            let ir_block = &view_ir.ir_block;
+            for access in analysis::env_accesses(ir_block) {
+                let location = Value::record(
+                    record! {
+                        "block_id" => Value::int(view_ir.block_id.get() as i64, head),
+                        "index" => Value::int(access.index as i64, head),
+                        "access" => Value::string(access.kind.to_string(), head),
+                        "instruction" => Value::string(
+                            &view_ir.formatted_instructions[access.index],
+                            head,
+                        ),
+                        "span" => ir_block
+                            .spans
+                            .get(access.index)
+                            .map(|span| span_value(*span, head))
+                            .unwrap_or(Value::nothing(head)),
+                    },
+                    head,
+                );
+                let position = match usage.iter().position(|used| used.name == access.name) {
+                    Some(position) => position,
+                    None => {
+                        usage.push(Usage {
+                            name: access.name,
+                            locations: vec![],
+                            reads: 0,
+                            writes: 0,
+                            optional: true,
+                        });
+                        usage.len() - 1
+                    }
+                };
+                let used = &mut usage[position];
+                used.locations.push(location);
+                match access.kind {
+                    EnvAccessKind::Write => used.writes += 1,
+                    EnvAccessKind::ReadOptional => used.reads += 1,
+                    EnvAccessKind::Read | EnvAccessKind::ReadAll => {
+                        used.reads += 1;
+                        used.optional = false;
+                    }
+                }
+            }
+        }
+
+        let rows = usage
+            .into_iter()
+            .map(|used| {
+                Value::record(
+                    record! {
+                        "name" => Value::string(used.name, head),
+                        "reads" => Value::int(used.reads as i64, head),
+                        "writes" => Value::int(used.writes as i64, head),
+                        "optional" => Value::bool(used.optional && used.reads > 0, head),
+                        "locations" => Value::list(used.locations, head),
+                    },
+                    head,
+                )
+            })
+            .collect();
+
+        Ok(Value::list(rows, head))
+    }
+}
@@ -0,0 +1,62 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{Category, Example, LabeledError, Signature, Type, Value};
+
+use crate::{analysis, ExploreIrPlugin};
+
+use super::{diagnostics_table, get_target, with_target};
+
+pub struct IrLint;
+
+impl SimplePluginCommand for IrLint {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir lint"
+    }
+
+    fn signature(&self) -> Signature {
+        with_target(Signature::build(PluginCommand::name(self)))
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "Find instruction patterns in the IR of a block or definition that do unnecessary work."
+    }
+
+    fn extra_description(&self) -> &str {
+        "Looks for clones of registers that are never used again, chains of moves that could be a \
+        single move, values that are collected twice in a row, and literals that are dropped \
+        without being used. These often point to script constructs that could be rewritten to run \
+        faster.
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir lint { $in | collect }",
+                description: "Lint the IR of the { $in | collect } block.",
+                result: None,
+            },
+            Example {
+                example: "ir lint 'std bench'",
+                description: "Lint the IR of the 'std bench' command.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let view_ir = get_target(engine, call)?;
+        let diagnostics = analysis::lint(&view_ir.ir_block);
+        Ok(diagnostics_table(&view_ir, diagnostics, call.head))
+    }
+}
@@ -0,0 +1,94 @@
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{record, LabeledError, Signature, Span, SyntaxShape, Value};
+
+use crate::{
+    analysis::Diagnostic,
+    data::{self, ViewIrOutput},
+};
+
+mod audit;
+mod consts;
+mod coverage;
+mod decompile;
+mod env;
+mod lint;
+mod stats;
+mod verify;
+
+pub use audit::IrAudit;
+pub use consts::IrConsts;
+pub use coverage::IrCoverage;
+pub use decompile::IrDecompile;
+pub use env::IrEnv;
+pub use lint::IrLint;
+pub use stats::IrStats;
+pub use verify::IrVerify;
+
+/// Add the arguments for choosing a block, which are the same as for `view ir`
+fn with_target(signature: Signature) -> Signature {
+    signature
+        .required(
+            "target",
+            SyntaxShape::Any,
+            "The name or block to analyze compiled code for.",
+        )
+        .switch(
+            "decl-id",
+            "Integer is a declaration ID rather than a block ID.",
+            Some('d'),
+        )
+}
+
+/// Get the IR for the block chosen by the arguments added by [`with_target`]
+fn get_target(
+    engine: &EngineInterface,
+    call: &EvaluatedCall,
+) -> Result<ViewIrOutput, LabeledError> {
+    let target = call.req(0)?;
+    let is_decl_id = call.has_flag("decl-id")?;
+    data::view_ir(engine, target, is_decl_id, call.head)
+}
+
+/// Convert a span to a record like the ones in `ast --flatten`
+fn span_value(span: Span, head: Span) -> Value {
+    Value::record(
+        record! {
+            "start" => Value::int(span.start as i64, head),
+            "end" => Value::int(span.end as i64, head),
+        },
+        head,
+    )
+}
+
+/// Make a table of diagnostics, with the instruction and span that each one refers to
+fn diagnostics_table(view_ir: &ViewIrOutput, diagnostics: Vec<Diagnostic>, head: Span) -> Value {
+    let ir_block = &view_ir.ir_block;
+    let rows = diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let index = diagnostic.index;
+            let span = index
+                .and_then(|index| ir_block.spans.get(index).copied())
+                .or(view_ir.span);
+            Value::record(
+                record! {
+                    "index" => index
+                        .map(|index| Value::int(index as i64, head))
+                        .unwrap_or(Value::nothing(head)),
+                    "severity" => Value::string(diagnostic.severity.to_string(), head),
+                    "message" => Value::string(diagnostic.message, head),
+                    "instruction" => index
+                        .and_then(|index| view_ir.formatted_instructions.get(index))
+                        .map(|inst| Value::string(inst, head))
+                        .unwrap_or(Value::nothing(head)),
+                    "span" => span
+                        .map(|span| span_value(span, head))
+                        .unwrap_or(Value::nothing(head)),
+                },
+                head,
+            )
+        })
+        .collect();
+
+    Value::list(rows, head)
+}
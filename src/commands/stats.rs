@@ -0,0 +1,199 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{
+    record, Category, Example, LabeledError, PipelineData, Signature, Span, Spanned, SyntaxShape,
+    Type, Value,
+};
+
+use crate::{
+    analysis,
+    data::{self, ViewIrOutput},
+    ExploreIrPlugin,
+};
+
+pub struct IrStats;
+
+impl SimplePluginCommand for IrStats {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir stats"
+    }
+
+    fn signature(&self) -> Signature {
+        Signature::build(PluginCommand::name(self))
+            .optional(
+                "target",
+                SyntaxShape::Any,
+                "The name or block to measure compiled code for.",
+            )
+            .switch(
+                "decl-id",
+                "Integer is a declaration ID rather than a block ID.",
+                Some('d'),
+            )
+            .named(
+                "module",
+                SyntaxShape::String,
+                "Measure every custom command in a module instead.",
+                Some('m'),
+            )
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "Measure the size and complexity of the IR of a block or definition, or of every command in a module."
+    }
+
+    fn extra_description(&self) -> &str {
+        "There's a row for the target block and for each block, closure and row condition nested \
+        in it, or with `--module`, a row for each custom command in the module. The columns are:
+  instructions          - the number of instructions
+  basic_blocks          - the number of straight-line runs of instructions
+  cyclomatic_complexity - the number of independent paths through the block
+  max_loop_depth        - how deeply loops are nested
+  calls                 - the number of calls to commands
+  registers             - the number of registers
+  error_handlers        - the number of error handlers pushed by `try`
+  data_size             - the size of the string data for literals, in bytes
+  opcodes               - how many instructions there are with each opcode, most common first
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir stats 'my command'",
+                description: "Measure a custom command and the closures in it.",
+                result: None,
+            },
+            Example {
+                example: "ir stats 'my command' | get 0.opcodes",
+                description: "Count the instructions of each kind in a custom command.",
+                result: None,
+            },
+            Example {
+                example: "ir stats --module my-module | sort-by cyclomatic_complexity --reverse",
+                description: "Find the most complicated commands in a module.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let head = call.head;
+
+        let blocks: Vec<(Option<String>, ViewIrOutput)> =
+            if let Some(module) = call.get_flag::<Spanned<String>>("module")? {
+                module_commands(engine, &module, head)?
+                    .into_iter()
+                    .map(|(name, view_ir)| (Some(name), view_ir))
+                    .collect()
+            } else if let Some(target) = call.opt::<Value>(0)? {
+                let name = target.as_str().ok().map(String::from);
+                let view_ir = data::view_ir(engine, target, call.has_flag("decl-id")?, head)?;
+                data::nested_blocks(engine, view_ir, head)?
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, view_ir)| (name.clone().filter(|_| position == 0), view_ir))
+                    .collect()
+            } else {
+                return Err(LabeledError::new("Nothing to measure")
+                    .with_label("needs a target or `--module`", head));
+            };
+
+        let rows = blocks
+            .into_iter()
+            .map(|(name, view_ir)| {
+                let stats = analysis::stats(&view_ir.ir_block);
+                let int = |value: usize| Value::int(value as i64, head);
+                Value::record(
+                    record! {
+                        "name" => name
+                            .map(|name| Value::string(name, head))
+                            .unwrap_or(Value::nothing(head)),
+                        "block_id" => int(view_ir.block_id.get()),
+                        "instructions" => int(stats.instructions),
+                        "basic_blocks" => int(stats.basic_blocks),
+                        "cyclomatic_complexity" => int(stats.cyclomatic_complexity),
+                        "max_loop_depth" => int(stats.max_loop_depth),
+                        "calls" => int(stats.calls),
+                        "registers" => int(stats.registers),
+                        "error_handlers" => int(stats.error_handlers),
+                        "data_size" => Value::filesize(stats.data_size as i64, head),
+                        "opcodes" => Value::record(
+                            analysis::opcode_histogram(&view_ir.formatted_instructions)
+                                .into_iter()
+                                .map(|(opcode, count)| (opcode, int(count)))
+                                .collect(),
+                            head,
+                        ),
+                    },
+                    head,
+                )
+            })
+            .collect();
+
+        Ok(Value::list(rows, head))
+    }
+}
+
+/// Get the IR of each custom command in a module from `scope modules`, with its name
+fn module_commands(
+    engine: &EngineInterface,
+    module: &Spanned<String>,
+    head: Span,
+) -> Result<Vec<(String, ViewIrOutput)>, LabeledError> {
+    let Some(decl_id) = engine.find_decl("scope modules")? else {
+        return Err(LabeledError::new("Can't find `scope modules`")
+            .with_label("must be in scope for `ir stats --module`", head));
+    };
+
+    let modules = engine
+        .call_decl(
+            decl_id,
+            EvaluatedCall::new(head),
+            PipelineData::Empty,
+            true,
+            false,
+        )?
+        .into_value(head)?
+        .into_list()?;
+
+    let Some(commands) = modules.iter().find_map(|found| {
+        let record = found.as_record().ok()?;
+        (record.get("name")?.as_str().ok()? == module.item)
+            .then(|| record.get("commands")?.as_list().ok())
+            .flatten()
+    }) else {
+        return Err(LabeledError::new("Module not found").with_label(
+            format!("there's no module named `{}`", module.item),
+            module.span,
+        ));
+    };
+
+    let mut blocks = vec![];
+    for command in commands {
+        let Ok(record) = command.as_record() else {
+            continue;
+        };
+        let (Some(name), Some(decl_id)) = (
+            record.get("name").and_then(|name| name.as_str().ok()),
+            record.get("decl_id"),
+        ) else {
+            continue;
+        };
+        // Only custom commands have IR
+        if let Ok(view_ir) = data::view_ir(engine, decl_id.clone(), true, head) {
+            blocks.push((name.to_owned(), view_ir));
+        }
+    }
+    Ok(blocks)
+}
@@ -0,0 +1,61 @@
+use nu_plugin::{EngineInterface, EvaluatedCall, PluginCommand, SimplePluginCommand};
+use nu_protocol::{Category, Example, LabeledError, Signature, Type, Value};
+
+use crate::{analysis, ExploreIrPlugin};
+
+use super::{diagnostics_table, get_target, with_target};
+
+pub struct IrVerify;
+
+impl SimplePluginCommand for IrVerify {
+    type Plugin = ExploreIrPlugin;
+
+    fn name(&self) -> &str {
+        "ir verify"
+    }
+
+    fn signature(&self) -> Signature {
+        with_target(Signature::build(PluginCommand::name(self)))
+            .input_output_type(Type::Nothing, Type::table())
+            .category(Category::Debug)
+    }
+
+    fn description(&self) -> &str {
+        "Check the IR of a block or definition for structural problems."
+    }
+
+    fn extra_description(&self) -> &str {
+        "Looks for registers that may be read before they're written, branch targets that are out \
+        of range, unbalanced error handlers, arguments or redirections left unused by a call, and \
+        mismatched lengths of the block's fields. These usually indicate a bug in the compiler.
+
+Accepts valid arguments for `view ir`. For more information, see `view ir --help`."
+    }
+
+    fn examples(&self) -> Vec<Example> {
+        vec![
+            Example {
+                example: "ir verify { 1 + 2 }",
+                description: "Check the IR of the { 1 + 2 } block.",
+                result: None,
+            },
+            Example {
+                example: "ir verify 'std bench'",
+                description: "Check the IR of the 'std bench' command.",
+                result: None,
+            },
+        ]
+    }
+
+    fn run(
+        &self,
+        _plugin: &ExploreIrPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        _input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let view_ir = get_target(engine, call)?;
+        let diagnostics = analysis::verify(&view_ir.ir_block);
+        Ok(diagnostics_table(&view_ir, diagnostics, call.head))
+    }
+}
@@ -1,8 +1,22 @@
+use std::{collections::HashMap, ops::Range};
+
 use nu_plugin::{EngineInterface, EvaluatedCall};
-use nu_protocol::{ir::IrBlock, BlockId, IntoSpanned, LabeledError, PipelineData, Span, Value};
-use ratatui::widgets::ListState;
+use nu_protocol::{
+    ir::{Instruction, IrBlock, Literal},
+    BlockId, DeclId, IntoSpanned, LabeledError, PipelineData, Span, Type, Value,
+};
+use ratatui::{style::Style, widgets::ListState};
 use serde::Deserialize;
 
+use crate::{
+    analysis::{self, Diagnostic, ErrorHandler, Loop, Signatures, Types},
+    ast::AstNode,
+    emulator::Emulator,
+    profile::{BranchCoverage, InstructionProfile},
+    pseudocode::PseudoLine,
+    syntax,
+};
+
 #[derive(Deserialize)]
 #[allow(unused)]
 pub struct ViewIrOutput {
@@ -12,7 +26,7 @@ pub struct ViewIrOutput {
     pub formatted_instructions: Vec<String>,
 }
 
-fn view_ir(
+pub fn view_ir(
     engine: &EngineInterface,
     target: Value,
     is_decl_id: bool,
@@ -44,16 +58,292 @@ fn view_ir(
     })
 }
 
+/// Get the IR for a block and every block, closure and row condition nested in it, with the
+/// outer blocks first
+pub fn nested_blocks(
+    engine: &EngineInterface,
+    view_ir: ViewIrOutput,
+    head: Span,
+) -> Result<Vec<ViewIrOutput>, LabeledError> {
+    collect_blocks(engine, view_ir, false, head)
+}
+
+/// Get the IR for a block and every block reachable from it, through nested blocks and calls to
+/// custom commands, in the order they're found
+pub fn reachable_blocks(
+    engine: &EngineInterface,
+    view_ir: ViewIrOutput,
+    head: Span,
+) -> Result<Vec<ViewIrOutput>, LabeledError> {
+    collect_blocks(engine, view_ir, true, head)
+}
+
+fn collect_blocks(
+    engine: &EngineInterface,
+    first: ViewIrOutput,
+    follow_calls: bool,
+    head: Span,
+) -> Result<Vec<ViewIrOutput>, LabeledError> {
+    let mut seen_blocks = vec![first.block_id];
+    let mut seen_decls = vec![];
+    let mut blocks = vec![first];
+    let mut next = 0;
+    while let Some(block) = blocks.get(next) {
+        let mut found = vec![];
+        for instruction in &block.ir_block.instructions {
+            match instruction {
+                Instruction::LoadLiteral {
+                    lit:
+                        Literal::Block(block_id)
+                        | Literal::Closure(block_id)
+                        | Literal::RowCondition(block_id),
+                    ..
+                } if !seen_blocks.contains(block_id) => {
+                    seen_blocks.push(*block_id);
+                    found.push(view_ir(
+                        engine,
+                        Value::int(block_id.get() as i64, head),
+                        false,
+                        head,
+                    )?);
+                }
+                Instruction::Call { decl_id, .. }
+                    if follow_calls && !seen_decls.contains(decl_id) =>
+                {
+                    seen_decls.push(*decl_id);
+                    // Only custom commands have IR, so anything else fails and is skipped
+                    if let Ok(view_ir) =
+                        view_ir(engine, Value::int(decl_id.get() as i64, head), true, head)
+                    {
+                        if !seen_blocks.contains(&view_ir.block_id) {
+                            seen_blocks.push(view_ir.block_id);
+                            found.push(view_ir);
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        blocks.extend(found);
+        next += 1;
+    }
+    Ok(blocks)
+}
+
+/// The file (or REPL entry) that a block's source code came from
+pub struct SourceFile {
+    pub name: String,
+    /// The line number in the file that the block starts on (starting from 1)
+    pub first_line: usize,
+    /// The column in the file that the block starts at (starting from 1)
+    pub first_column: usize,
+}
+
+/// Find the file containing `span` from the output of `view files`, and where in it the span begins
+fn source_file(
+    engine: &EngineInterface,
+    span: Span,
+    head: Span,
+) -> Result<Option<SourceFile>, LabeledError> {
+    let Some(decl_id) = engine.find_decl("view files")? else {
+        return Ok(None);
+    };
+
+    let files = engine
+        .call_decl(
+            decl_id,
+            EvaluatedCall::new(head),
+            PipelineData::Empty,
+            true,
+            false,
+        )?
+        .into_value(head)?
+        .into_list()?;
+
+    for file in files {
+        let Ok(record) = file.into_record() else {
+            continue;
+        };
+        let int_field = |name: &str| {
+            record
+                .get(name)
+                .and_then(|value| value.as_int().ok())
+                .and_then(|value| usize::try_from(value).ok())
+        };
+        let (Some(start), Some(end)) = (int_field("start"), int_field("end")) else {
+            continue;
+        };
+
+        if start <= span.start && span.end <= end {
+            let name = record
+                .get("filename")
+                .and_then(|value| value.as_str().ok())
+                .unwrap_or_default()
+                .to_owned();
+
+            // Count the lines before the block to get its real position in the file
+            let before = engine.get_span_contents(Span::new(start, span.start))?;
+            let before = String::from_utf8_lossy(&before);
+            let first_line = before.matches('\n').count() + 1;
+            let first_column = before
+                .rsplit('\n')
+                .next()
+                .map(|line| line.chars().count())
+                .unwrap_or(0)
+                + 1;
+
+            return Ok(Some(SourceFile {
+                name,
+                first_line,
+                first_column,
+            }));
+        }
+    }
+
+    Ok(None)
+}
+
+/// A command listed by `scope commands`
+pub struct CommandInfo {
+    pub name: String,
+    pub category: String,
+    /// The input and output types of each of the command's signatures
+    pub signatures: Vec<(Type, Type)>,
+}
+
+/// Get every command from `scope commands` by its declaration ID. That lists every command in
+/// full, so it's only worth running once.
+pub fn commands(
+    engine: &EngineInterface,
+    head: Span,
+) -> Result<HashMap<DeclId, CommandInfo>, LabeledError> {
+    let Some(decl_id) = engine.find_decl("scope commands")? else {
+        return Err(LabeledError::new("Can't find `scope commands`")
+            .with_label("must be in scope to look up commands", head));
+    };
+
+    let commands = engine
+        .call_decl(
+            decl_id,
+            EvaluatedCall::new(head),
+            PipelineData::Empty,
+            true,
+            false,
+        )?
+        .into_value(head)?
+        .into_list()?;
+
+    Ok(commands
+        .iter()
+        .filter_map(|command| {
+            let record = command.as_record().ok()?;
+            let decl_id = DeclId::new(usize::try_from(record.get("decl_id")?.as_int().ok()?).ok()?);
+            let name = record.get("name")?.as_str().ok()?.to_owned();
+            let category = record.get("category")?.as_str().ok()?.to_owned();
+            // Each signature is a list of parameters, including the input and output
+            let mut signatures = vec![];
+            if let Some(Value::Record { val, .. }) = record.get("signatures") {
+                for (_, parameters) in val.iter() {
+                    let shape = |kind: &str| {
+                        parameters.as_list().ok()?.iter().find_map(|parameter| {
+                            let parameter = parameter.as_record().ok()?;
+                            (parameter.get("parameter_type")?.as_str().ok()? == kind)
+                                .then(|| parameter.get("syntax_shape")?.as_str().ok())
+                                .flatten()
+                        })
+                    };
+                    if let (Some(input), Some(output)) = (shape("input"), shape("output")) {
+                        signatures
+                            .push((analysis::parse_type(input), analysis::parse_type(output)));
+                    }
+                }
+            }
+            Some((
+                decl_id,
+                CommandInfo {
+                    name,
+                    category,
+                    signatures,
+                },
+            ))
+        })
+        .collect())
+}
+
+/// Get the input and output types of every command, for inferring types
+pub fn signatures(engine: &EngineInterface, head: Span) -> Result<Signatures, LabeledError> {
+    Ok(commands(engine, head)?
+        .into_iter()
+        .map(|(decl_id, command)| (decl_id, command.signatures))
+        .collect())
+}
+
+/// A range of instructions that can be folded into one line in the instruction list
+pub struct Fold {
+    pub range: Range<usize>,
+    /// The instruction that's still shown when the range is folded
+    pub shown: usize,
+    pub folded: bool,
+    /// Whether the fold was made automatically for the arguments of a call
+    pub auto: bool,
+}
+
+/// Instructions picked out of the instruction list, which hides all of the others
+pub struct Filter {
+    /// What the filter matches, shown in the title of the list
+    pub description: String,
+    pub indices: Vec<usize>,
+}
+
 pub struct BlockState {
     pub view_ir: ViewIrOutput,
     pub source: String,
+    pub source_file: Option<SourceFile>,
+    /// Syntax highlighting for `source`
+    pub source_highlights: Vec<(Range<usize>, Style)>,
+    /// Warnings from [`analysis::lint`]
+    pub lints: Vec<Diagnostic>,
+    /// The reason each instruction can't be reached, from [`analysis::unreachable`]
+    pub unreachable: Vec<Option<String>>,
+    /// The error handlers that are pushed on entry to each instruction, innermost last
+    pub error_handlers: Vec<Vec<ErrorHandler>>,
+    /// Loops found by [`analysis::loops`], outer loops first
+    pub loops: Vec<Loop>,
+    /// Which loops are collapsed in the loop outline
+    pub collapsed_loops: Vec<bool>,
+    /// Index into `loops` of the loop under the cursor in the loop outline
+    pub loop_cursor: usize,
+    /// The value written by each instruction if it's constant, from [`analysis::constants`]
+    pub constants: Vec<Option<Value>>,
+    /// The types inferred for each register, from [`analysis::infer_types`]
+    pub types: Types,
+    /// Decompiled code for the block, decompiled when it's first shown
+    pub pseudocode: Option<Vec<PseudoLine>>,
+    /// Folded ranges in the instruction list, starting with the arguments of each call
+    pub folds: Vec<Fold>,
+    /// Only show some of the instructions, ignoring folds
+    pub filter: Option<Filter>,
+    /// How often each instruction ran and how long it took, if the block was profiled
+    pub profile: Vec<InstructionProfile>,
+    /// Which ways each conditional branch went while profiling
+    pub branches: Vec<BranchCoverage>,
+    /// Stepping through the block, if it's being emulated
+    pub emulator: Option<Emulator>,
+    /// How many of each opcode there are in every block reachable from this one, loaded when
+    /// it's first shown
+    pub reachable_opcodes: Option<Vec<(String, usize)>>,
+    /// The parsed AST of `source`, loaded when it's first shown
+    pub ast: Option<Result<Vec<AstNode>, String>>,
     pub list_state: ListState,
+    /// Byte offset into `source` of the cursor used when the source pane is focused
+    pub source_cursor: usize,
 }
 
 pub fn get(
     engine: &EngineInterface,
     target: Value,
     is_decl_id: bool,
+    signatures: &Signatures,
     head: Span,
 ) -> Result<BlockState, LabeledError> {
     let view_ir = view_ir(engine, target, is_decl_id, head)?;
@@ -64,9 +354,125 @@ pub fn get(
         String::new()
     };
 
+    // The file is only needed to open the editor, so it's fine if it can't be found
+    let source_file = view_ir
+        .span
+        .and_then(|span| source_file(engine, span, head).ok().flatten());
+
+    // Highlighting is just nice to have, so don't fail if it doesn't work
+    let source_highlights = if !source.is_empty() {
+        syntax::highlight(engine, &source, head).unwrap_or_default()
+    } else {
+        vec![]
+    };
+
+    let lints = analysis::lint(&view_ir.ir_block);
+    let unreachable = analysis::unreachable(&view_ir.ir_block.instructions);
+    let error_handlers = analysis::error_handlers(&view_ir.ir_block.instructions);
+    let loops = analysis::loops(&view_ir.ir_block.instructions);
+    let constants = analysis::constants(&view_ir.ir_block);
+    let types = analysis::infer_types(&view_ir.ir_block, signatures);
+    let folds = analysis::call_setup_ranges(&view_ir.ir_block.instructions)
+        .into_iter()
+        .map(|range| Fold {
+            shown: range.end - 1,
+            range,
+            folded: true,
+            auto: true,
+        })
+        .collect();
+
     Ok(BlockState {
         view_ir,
         source,
+        source_file,
+        source_highlights,
+        lints,
+        unreachable,
+        error_handlers,
+        collapsed_loops: vec![false; loops.len()],
+        loops,
+        loop_cursor: 0,
+        constants,
+        types,
+        pseudocode: None,
+        folds,
+        filter: None,
+        profile: vec![],
+        branches: vec![],
+        emulator: None,
+        reachable_opcodes: None,
+        ast: None,
         list_state: ListState::default(),
+        source_cursor: 0,
     })
 }
+
+impl BlockState {
+    /// Get the indices of the instructions that are shown in the instruction list, which are the
+    /// ones that match the filter, or aren't hidden by a fold
+    pub fn visible_instructions(&self) -> Vec<usize> {
+        if let Some(filter) = &self.filter {
+            return filter.indices.clone();
+        }
+        (0..self.view_ir.ir_block.instructions.len())
+            .filter(|&index| {
+                !self
+                    .folds
+                    .iter()
+                    .any(|fold| fold.folded && fold.range.contains(&index) && fold.shown != index)
+            })
+            .collect()
+    }
+
+    /// Unfold any folds that hide an instruction, and remove the filter if it doesn't match it
+    pub fn reveal(&mut self, index: usize) {
+        if self
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !filter.indices.contains(&index))
+        {
+            self.filter = None;
+        }
+        for fold in &mut self.folds {
+            if fold.folded && fold.range.contains(&index) && fold.shown != index {
+                fold.folded = false;
+            }
+        }
+    }
+
+    /// Get the loops that are shown in the loop outline, which are the ones that aren't inside of
+    /// a collapsed loop
+    pub fn visible_loops(&self) -> Vec<usize> {
+        (0..self.loops.len())
+            .filter(|&index| {
+                let mut parent = self.loops[index].parent;
+                while let Some(ancestor) = parent {
+                    if self.collapsed_loops[ancestor] {
+                        return false;
+                    }
+                    parent = self.loops[ancestor].parent;
+                }
+                true
+            })
+            .collect()
+    }
+
+    /// Get the line and column (both starting from 1) in the source file for an offset into
+    /// `source`
+    pub fn source_location(&self, offset: usize) -> Option<(usize, usize)> {
+        let file = self.source_file.as_ref()?;
+        let before = self.source.get(..offset)?;
+        let line_index = before.matches('\n').count();
+        let column = before
+            .rsplit('\n')
+            .next()
+            .map(|line| line.chars().count())
+            .unwrap_or(0);
+        if line_index == 0 {
+            Some((file.first_line, file.first_column + column))
+        } else {
+            Some((file.first_line + line_index, column + 1))
+        }
+    }
+}
@@ -0,0 +1,521 @@
+//! A step-by-step interpreter for the instructions of a block, for watching what the IR does with
+//! concrete values
+
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{
+    engine::{Closure, Matcher},
+    ir::{Instruction, IrBlock, Literal},
+    IntoSpanned, PipelineData, Range, Record, RegId, ShellError, Span, Value, VarId,
+    ENV_VARIABLE_ID, IN_VARIABLE_ID,
+};
+
+use crate::analysis;
+
+/// The most items of a range that can be iterated over
+const MAX_RANGE_ITEMS: usize = 100_000;
+
+/// Whether the emulator can keep going
+pub enum Status {
+    Running,
+    Returned(Value),
+    /// An error was thrown with no error handler to catch it
+    Failed(String),
+    /// The next instruction can't be emulated, so it stopped before it
+    Unsupported(String),
+}
+
+/// An argument pushed for the next call
+pub enum Argument {
+    Positional(Value),
+    Spread(Value),
+    Flag(String),
+    Named(String, Value),
+}
+
+/// What to do after an instruction
+enum Flow {
+    Next,
+    Jump(usize),
+    Return(Value),
+}
+
+/// Why an instruction couldn't finish
+enum Stop {
+    Error(ShellError),
+    Unsupported(String),
+}
+
+impl From<ShellError> for Stop {
+    fn from(error: ShellError) -> Stop {
+        Stop::Error(error)
+    }
+}
+
+pub struct Emulator {
+    /// The index of the next instruction to run
+    pub pc: usize,
+    /// The value in each register, or `None` if it's empty
+    pub registers: Vec<Option<Value>>,
+    /// Variables that have been stored, in the order they were first stored
+    pub variables: Vec<(VarId, Value)>,
+    /// Environment variables that have been set, which hide the ones from the engine
+    pub env: Vec<(String, Value)>,
+    /// Arguments pushed for the next call
+    pub arguments: Vec<Argument>,
+    /// The error handlers that have been pushed, innermost last, with the register the error
+    /// should be put in
+    pub error_handlers: Vec<(usize, Option<RegId>)>,
+    pub status: Status,
+    /// The number of instructions that have run
+    pub steps: usize,
+}
+
+impl Emulator {
+    pub fn new(register_count: u32) -> Emulator {
+        Emulator {
+            pc: 0,
+            registers: vec![None; register_count as usize],
+            variables: vec![],
+            env: vec![],
+            arguments: vec![],
+            error_handlers: vec![],
+            status: Status::Running,
+            steps: 0,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.status, Status::Running)
+    }
+
+    /// Run instructions until the block returns or fails, or `limit` instructions have run
+    pub fn run(&mut self, engine: &EngineInterface, ir_block: &IrBlock, limit: usize) {
+        for _ in 0..limit {
+            if !self.is_running() {
+                break;
+            }
+            self.step(engine, ir_block);
+        }
+    }
+
+    /// Run the next instruction
+    pub fn step(&mut self, engine: &EngineInterface, ir_block: &IrBlock) {
+        if !self.is_running() {
+            return;
+        }
+        let Some(instruction) = ir_block.instructions.get(self.pc) else {
+            self.status = Status::Failed(format!("instruction {} is out of range", self.pc));
+            return;
+        };
+        let span = ir_block
+            .spans
+            .get(self.pc)
+            .copied()
+            .unwrap_or(Span::unknown());
+
+        match self.execute(engine, ir_block, instruction, span) {
+            Ok(Flow::Next) => self.pc += 1,
+            Ok(Flow::Jump(target)) => self.pc = target,
+            Ok(Flow::Return(value)) => self.status = Status::Returned(value),
+            Err(Stop::Unsupported(reason)) => {
+                self.status = Status::Unsupported(reason);
+                return;
+            }
+            Err(Stop::Error(error)) => match self.error_handlers.pop() {
+                Some((handler, error_register)) => {
+                    if let Some(reg_id) = error_register {
+                        self.put(reg_id, error.into_value(span, false));
+                    }
+                    self.pc = handler;
+                }
+                None => self.status = Status::Failed(error.to_string()),
+            },
+        }
+        self.steps += 1;
+    }
+
+    fn take(&mut self, reg_id: RegId) -> Option<Value> {
+        self.registers.get_mut(reg_id.get() as usize)?.take()
+    }
+
+    fn borrow(&self, reg_id: RegId) -> Option<&Value> {
+        self.registers.get(reg_id.get() as usize)?.as_ref()
+    }
+
+    /// Take the value out of a register, treating an empty register as `null` like the engine does
+    fn collect(&mut self, reg_id: RegId, span: Span) -> Value {
+        self.take(reg_id).unwrap_or(Value::nothing(span))
+    }
+
+    fn put(&mut self, reg_id: RegId, value: Value) {
+        if let Some(register) = self.registers.get_mut(reg_id.get() as usize) {
+            *register = Some(value);
+        }
+    }
+
+    fn variable(&self, var_id: VarId) -> Option<&Value> {
+        self.variables
+            .iter()
+            .find(|(id, _)| *id == var_id)
+            .map(|(_, value)| value)
+    }
+
+    fn set_variable(&mut self, var_id: VarId, value: Value) {
+        match self.variables.iter_mut().find(|(id, _)| *id == var_id) {
+            Some((_, existing)) => *existing = value,
+            None => self.variables.push((var_id, value)),
+        }
+    }
+
+    fn env_var(&self, engine: &EngineInterface, key: &str) -> Result<Option<Value>, ShellError> {
+        match self.env.iter().find(|(name, _)| name == key) {
+            Some((_, value)) => Ok(Some(value.clone())),
+            None => engine.get_env_var(key),
+        }
+    }
+
+    fn execute(
+        &mut self,
+        engine: &EngineInterface,
+        ir_block: &IrBlock,
+        instruction: &Instruction,
+        span: Span,
+    ) -> Result<Flow, Stop> {
+        let string = |slice| {
+            analysis::data_str(ir_block, slice)
+                .map(String::from)
+                .ok_or_else(|| Stop::Unsupported("a literal outside of the block's data".into()))
+        };
+        let unsupported = |what: &str| Err(Stop::Unsupported(format!("{what} isn't supported")));
+
+        match instruction {
+            Instruction::Unreachable => {
+                return Err(Stop::Unsupported("reached `unreachable`".into()));
+            }
+            Instruction::LoadLiteral { dst, lit } => {
+                let value = match lit {
+                    Literal::Range {
+                        start,
+                        step,
+                        end,
+                        inclusion,
+                    } => {
+                        let start = self.collect(*start, span);
+                        let step = self.collect(*step, span);
+                        let end = self.collect(*end, span);
+                        Value::range(Range::new(start, step, end, *inclusion, span)?, span)
+                    }
+                    Literal::List { capacity } => Value::list(Vec::with_capacity(*capacity), span),
+                    Literal::Record { capacity } => {
+                        Value::record(Record::with_capacity(*capacity), span)
+                    }
+                    // Paths aren't expanded, since that depends on the engine's current directory
+                    Literal::Filepath { val, .. } | Literal::Directory { val, .. } => {
+                        Value::string(string(*val)?, span)
+                    }
+                    Literal::GlobPattern { val, no_expand } => {
+                        Value::glob(string(*val)?, *no_expand, span)
+                    }
+                    // The block's captures aren't known here, so every variable set so far is
+                    // captured instead
+                    Literal::Block(block_id)
+                    | Literal::Closure(block_id)
+                    | Literal::RowCondition(block_id) => Value::closure(
+                        Closure {
+                            block_id: *block_id,
+                            captures: self.variables.clone(),
+                        },
+                        span,
+                    ),
+                    lit => match analysis::literal_value(ir_block, lit) {
+                        Some(value) => value.with_span(span),
+                        None => return unsupported("this literal"),
+                    },
+                };
+                self.put(*dst, value);
+            }
+            Instruction::LoadValue { dst, val } => self.put(*dst, (**val).clone()),
+            Instruction::Move { dst, src } => {
+                let value = self.collect(*src, span);
+                self.put(*dst, value);
+            }
+            Instruction::Clone { dst, src } => {
+                let value = self.borrow(*src).cloned().unwrap_or(Value::nothing(span));
+                self.put(*dst, value);
+            }
+            Instruction::Collect { .. } | Instruction::Span { .. } => (),
+            Instruction::Drop { src } | Instruction::Drain { src } => {
+                self.take(*src);
+            }
+            Instruction::DrainIfEnd { .. } => (),
+            Instruction::LoadVariable { dst, var_id } => {
+                let value = match self.variable(*var_id) {
+                    Some(value) => value.clone(),
+                    None if *var_id == ENV_VARIABLE_ID => {
+                        let mut record: Record = engine.get_env_vars()?.into_iter().collect();
+                        for (name, value) in &self.env {
+                            record.insert(name, value.clone());
+                        }
+                        Value::record(record, span)
+                    }
+                    None if *var_id == IN_VARIABLE_ID => Value::nothing(span),
+                    None => {
+                        return Err(Stop::Unsupported(format!(
+                            "variable {} was set outside of the block",
+                            var_id.get()
+                        )));
+                    }
+                };
+                self.put(*dst, value);
+            }
+            Instruction::StoreVariable { var_id, src } => {
+                let value = self.collect(*src, span);
+                self.set_variable(*var_id, value);
+            }
+            Instruction::DropVariable { var_id } => {
+                self.variables.retain(|(id, _)| id != var_id);
+            }
+            Instruction::LoadEnv { dst, key } | Instruction::LoadEnvOpt { dst, key } => {
+                let key = string(*key)?;
+                let value = match self.env_var(engine, &key)? {
+                    Some(value) => value,
+                    None if matches!(instruction, Instruction::LoadEnvOpt { .. }) => {
+                        Value::nothing(span)
+                    }
+                    None => {
+                        return Err(Stop::Error(ShellError::EnvVarNotFoundAtRuntime {
+                            envvar_name: key,
+                            span,
+                        }));
+                    }
+                };
+                self.put(*dst, value);
+            }
+            Instruction::StoreEnv { key, src } => {
+                let key = string(*key)?;
+                let value = self.collect(*src, span);
+                self.env.retain(|(name, _)| *name != key);
+                self.env.push((key, value));
+            }
+            Instruction::PushPositional { src } => {
+                let value = self.collect(*src, span);
+                self.arguments.push(Argument::Positional(value));
+            }
+            Instruction::AppendRest { src } => {
+                let value = self.collect(*src, span);
+                self.arguments.push(Argument::Spread(value));
+            }
+            Instruction::PushFlag { name } => self.arguments.push(Argument::Flag(string(*name)?)),
+            Instruction::PushNamed { name, src } => {
+                let value = self.collect(*src, span);
+                self.arguments.push(Argument::Named(string(*name)?, value));
+            }
+            Instruction::PushShortFlag { .. } | Instruction::PushShortNamed { .. } => {
+                return unsupported("a flag without a long name");
+            }
+            Instruction::PushParserInfo { .. }
+            | Instruction::RedirectOut { .. }
+            | Instruction::RedirectErr { .. }
+            | Instruction::CheckErrRedirected { .. } => (),
+            Instruction::OpenFile { .. }
+            | Instruction::WriteFile { .. }
+            | Instruction::CloseFile { .. } => {
+                return unsupported("redirecting to a file");
+            }
+            Instruction::Call { decl_id, src_dst } => {
+                let mut call = EvaluatedCall::new(span);
+                for argument in std::mem::take(&mut self.arguments) {
+                    match argument {
+                        Argument::Positional(value) => {
+                            call.add_positional(value);
+                        }
+                        Argument::Spread(value) => {
+                            for item in value.into_list()? {
+                                call.add_positional(item);
+                            }
+                        }
+                        Argument::Flag(name) => {
+                            call.add_flag(name.into_spanned(span));
+                        }
+                        Argument::Named(name, value) => {
+                            call.add_named(name.into_spanned(span), value);
+                        }
+                    }
+                }
+                let input = match self.take(*src_dst) {
+                    Some(value) => PipelineData::Value(value, None),
+                    None => PipelineData::Empty,
+                };
+                let output = engine.call_decl(*decl_id, call, input, true, false)?;
+                let value = output.into_value(span)?;
+                self.put(*src_dst, value);
+            }
+            Instruction::StringAppend { src_dst, val } => {
+                let mut string = self.collect(*src_dst, span).into_string()?;
+                match self.collect(*val, span) {
+                    Value::String { val, .. } => string.push_str(&val),
+                    val => string.push_str(&val.to_expanded_string(", ", &*engine.get_config()?)),
+                }
+                self.put(*src_dst, Value::string(string, span));
+            }
+            Instruction::GlobFrom { src_dst, no_expand } => {
+                let value = match self.collect(*src_dst, span) {
+                    glob @ Value::Glob { .. } => glob,
+                    value => Value::glob(value.into_string()?, *no_expand, span),
+                };
+                self.put(*src_dst, value);
+            }
+            Instruction::ListPush { src_dst, item } => {
+                let item = self.collect(*item, span);
+                let mut list = self.collect(*src_dst, span).into_list()?;
+                list.push(item);
+                self.put(*src_dst, Value::list(list, span));
+            }
+            Instruction::ListSpread { src_dst, items } => {
+                let items = self.collect(*items, span).into_list()?;
+                let mut list = self.collect(*src_dst, span).into_list()?;
+                list.extend(items);
+                self.put(*src_dst, Value::list(list, span));
+            }
+            Instruction::RecordInsert { src_dst, key, val } => {
+                let key = self.collect(*key, span).coerce_into_string()?;
+                let val = self.collect(*val, span);
+                let mut record = self.collect(*src_dst, span).into_record()?;
+                if record.contains(&key) {
+                    return Err(Stop::Error(ShellError::ColumnDefinedTwice {
+                        col_name: key,
+                        second_use: span,
+                        first_use: span,
+                    }));
+                }
+                record.push(key, val);
+                self.put(*src_dst, Value::record(record, span));
+            }
+            Instruction::RecordSpread { src_dst, items } => {
+                let items = self.collect(*items, span).into_record()?;
+                let mut record = self.collect(*src_dst, span).into_record()?;
+                for (key, val) in items {
+                    record.insert(key, val);
+                }
+                self.put(*src_dst, Value::record(record, span));
+            }
+            Instruction::Not { src_dst } => {
+                let value = self.collect(*src_dst, span);
+                self.put(*src_dst, Value::bool(!value.as_bool()?, span));
+            }
+            Instruction::BinaryOp { lhs_dst, op, rhs } => {
+                let lhs = self.collect(*lhs_dst, span);
+                let rhs = self.collect(*rhs, span);
+                let Some(result) = analysis::binary_op(op, &lhs, &rhs, span) else {
+                    return unsupported("this operator");
+                };
+                self.put(*lhs_dst, result?);
+            }
+            Instruction::FollowCellPath { src_dst, path } => {
+                let path = self.cell_path(*path, span)?;
+                let value = self.collect(*src_dst, span);
+                self.put(*src_dst, value.follow_cell_path(&path.members, false)?);
+            }
+            Instruction::CloneCellPath { dst, src, path } => {
+                let path = self.cell_path(*path, span)?;
+                let value = self.borrow(*src).cloned().unwrap_or(Value::nothing(span));
+                self.put(*dst, value.follow_cell_path(&path.members, false)?);
+            }
+            Instruction::UpsertCellPath {
+                src_dst,
+                path,
+                new_value,
+            } => {
+                let path = self.cell_path(*path, span)?;
+                let new_value = self.collect(*new_value, span);
+                let mut value = self.collect(*src_dst, span);
+                value.upsert_data_at_cell_path(&path.members, new_value)?;
+                self.put(*src_dst, value);
+            }
+            Instruction::Jump { index } => return Ok(Flow::Jump(*index)),
+            Instruction::BranchIf { cond, index } => {
+                if self.collect(*cond, span).as_bool()? {
+                    return Ok(Flow::Jump(*index));
+                }
+            }
+            Instruction::BranchIfEmpty { src, index } => {
+                if self.borrow(*src).is_none() {
+                    return Ok(Flow::Jump(*index));
+                }
+            }
+            Instruction::Match {
+                pattern,
+                src,
+                index,
+            } => {
+                let value = self.borrow(*src).cloned().unwrap_or(Value::nothing(span));
+                let mut matches = vec![];
+                if pattern.match_value(&value, &mut matches) {
+                    for (var_id, value) in matches {
+                        self.set_variable(var_id, value);
+                    }
+                    return Ok(Flow::Jump(*index));
+                }
+            }
+            Instruction::CheckMatchGuard { src } => {
+                if !matches!(self.borrow(*src), Some(Value::Bool { .. })) {
+                    return Err(Stop::Error(ShellError::MatchGuardNotBool { span }));
+                }
+            }
+            Instruction::Iterate {
+                dst,
+                stream,
+                end_index,
+            } => {
+                // The rest of the stream is kept as a list in the register
+                let mut items = match self.take(*stream) {
+                    Some(Value::List { vals, .. }) => vals,
+                    // Ranges could be infinite, so only so much of one is taken
+                    Some(value @ Value::Range { .. }) => {
+                        let items: Vec<Value> = PipelineData::Value(value, None)
+                            .into_iter()
+                            .take(MAX_RANGE_ITEMS + 1)
+                            .collect();
+                        if items.len() > MAX_RANGE_ITEMS {
+                            return Err(Stop::Unsupported(format!(
+                                "iterating over more than {MAX_RANGE_ITEMS} items of a range isn't \
+                                supported"
+                            )));
+                        }
+                        items
+                    }
+                    Some(value) => vec![value],
+                    None => vec![],
+                };
+                if items.is_empty() {
+                    self.take(*dst);
+                    return Ok(Flow::Jump(*end_index));
+                }
+                let item = items.remove(0);
+                self.put(*stream, Value::list(items, span));
+                self.put(*dst, item);
+            }
+            Instruction::OnError { index } => self.error_handlers.push((*index, None)),
+            Instruction::OnErrorInto { index, dst } => {
+                self.error_handlers.push((*index, Some(*dst)))
+            }
+            Instruction::PopErrorHandler => {
+                self.error_handlers.pop();
+            }
+            Instruction::ReturnEarly { src } | Instruction::Return { src } => {
+                return Ok(Flow::Return(self.collect(*src, span)));
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn cell_path(&mut self, reg_id: RegId, span: Span) -> Result<nu_protocol::ast::CellPath, Stop> {
+        match self.collect(reg_id, span) {
+            Value::CellPath { val, .. } => Ok(val),
+            value => Err(Stop::Error(ShellError::TypeMismatch {
+                err_message: "expected cell path".into(),
+                span: value.span(),
+            })),
+        }
+    }
+}
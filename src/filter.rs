@@ -0,0 +1,199 @@
+//! Parse the queries typed into the filter prompt, and find the instructions that match them
+
+use nu_protocol::{ir::Instruction, RegId, VarId};
+
+use crate::{
+    analysis::{self, registers},
+    data::{BlockState, Filter},
+    pseudocode::Pseudocode,
+};
+
+/// What to look for in the instructions
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Query {
+    /// `load-env store-env`: instructions with any of the opcodes
+    Opcodes(Vec<String>),
+    /// `%3`: instructions that read or write the register
+    Register(RegId),
+    /// `var 5`: instructions that load, store or drop the variable
+    Variable(VarId),
+    /// `$name`: like [`Query::Variable`], for the variables with the name
+    VariableName(String),
+    /// `decl name`: calls to the declaration with the name
+    Decl(String),
+    /// `src text`: instructions compiled from code that contains the text
+    Source(String),
+}
+
+impl Query {
+    fn parse(text: &str) -> Result<Query, String> {
+        let text = text.trim();
+        let (word, rest) = text
+            .split_once(' ')
+            .map(|(word, rest)| (word, rest.trim()))
+            .unwrap_or((text, ""));
+        match word {
+            "var" => rest
+                .parse()
+                .map(|id| Query::Variable(VarId::new(id)))
+                .map_err(|_| format!("`{rest}` isn't a variable id")),
+            "decl" if !rest.is_empty() => Ok(Query::Decl(rest.into())),
+            "src" if !rest.is_empty() => Ok(Query::Source(rest.into())),
+            "decl" | "src" => Err(format!("`{word}` needs something to look for")),
+            _ if word.starts_with('%') && rest.is_empty() => word[1..]
+                .parse()
+                .map(|reg| Query::Register(RegId::new(reg)))
+                .map_err(|_| format!("`{word}` isn't a register")),
+            _ if word.starts_with('$') && rest.is_empty() => Ok(Query::VariableName(word.into())),
+            _ => Ok(Query::Opcodes(
+                text.split([' ', ','])
+                    .filter(|opcode| !opcode.is_empty())
+                    .map(String::from)
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// The variable that an instruction uses, if any
+fn variable(instruction: &Instruction) -> Option<VarId> {
+    match instruction {
+        Instruction::LoadVariable { var_id, .. }
+        | Instruction::StoreVariable { var_id, .. }
+        | Instruction::DropVariable { var_id } => Some(*var_id),
+        _ => None,
+    }
+}
+
+/// The source code that an instruction was compiled from
+fn source(block: &BlockState, index: usize) -> Option<&str> {
+    let block_span = block.view_ir.span?;
+    let span = block.view_ir.ir_block.spans.get(index)?;
+    block
+        .source
+        .get(span.start.checked_sub(block_span.start)?..span.end.checked_sub(block_span.start)?)
+}
+
+/// Find the variables with a name. Only variables in scope in the engine are named in the
+/// formatted instructions, so local variables are found by the source code that loads them.
+fn variables_named(block: &BlockState, name: &str) -> Vec<VarId> {
+    let pseudocode = Pseudocode::new(&block.view_ir);
+    let mut var_ids = vec![];
+    for (index, instruction) in block.view_ir.ir_block.instructions.iter().enumerate() {
+        let Some(var_id) = variable(instruction) else {
+            continue;
+        };
+        let named = pseudocode.name(index).is_some_and(|found| found == name)
+            || (matches!(instruction, Instruction::LoadVariable { .. })
+                && source(block, index) == Some(name));
+        if named && !var_ids.contains(&var_id) {
+            var_ids.push(var_id);
+        }
+    }
+    var_ids
+}
+
+/// Find the instructions in a block that match a query typed into the filter prompt
+pub fn filter(block: &BlockState, text: &str) -> Result<Filter, String> {
+    let query = Query::parse(text)?;
+    let view_ir = &block.view_ir;
+    let ir_block = &view_ir.ir_block;
+    let pseudocode = Pseudocode::new(view_ir);
+    let named = match &query {
+        Query::VariableName(name) => variables_named(block, name),
+        _ => vec![],
+    };
+
+    let matches = |index: usize| {
+        let instruction = &ir_block.instructions[index];
+        match &query {
+            Query::Opcodes(opcodes) => {
+                let opcode = analysis::opcode(&view_ir.formatted_instructions[index]);
+                opcodes.iter().any(|name| name == opcode)
+            }
+            Query::Register(reg_id) => {
+                registers::uses(instruction).contains(reg_id)
+                    || registers::defs(instruction).contains(reg_id)
+            }
+            Query::Variable(var_id) => variable(instruction) == Some(*var_id),
+            Query::VariableName(_) => {
+                variable(instruction).is_some_and(|var_id| named.contains(&var_id))
+            }
+            Query::Decl(name) => {
+                matches!(instruction, Instruction::Call { .. })
+                    && pseudocode.name(index).as_ref() == Some(name)
+            }
+            Query::Source(needle) => {
+                source(block, index).is_some_and(|source| source.contains(needle.as_str()))
+            }
+        }
+    };
+
+    let indices: Vec<usize> = (0..ir_block.instructions.len())
+        .filter(|&index| matches(index))
+        .collect();
+    if indices.is_empty() {
+        return Err(format!("no instructions match `{}`", text.trim()));
+    }
+    Ok(Filter {
+        description: text.trim().into(),
+        indices,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<Query, String> {
+        Query::parse(text)
+    }
+
+    #[test]
+    fn parses_opcodes() {
+        assert_eq!(
+            parse(" load-env,store-env  call "),
+            Ok(Query::Opcodes(vec![
+                "load-env".into(),
+                "store-env".into(),
+                "call".into()
+            ]))
+        );
+    }
+
+    #[test]
+    fn parses_registers() {
+        assert_eq!(parse("%3"), Ok(Query::Register(RegId::new(3))));
+        assert_eq!(parse("%x"), Err("`%x` isn't a register".into()));
+    }
+
+    #[test]
+    fn parses_variables() {
+        assert_eq!(parse("var 5"), Ok(Query::Variable(VarId::new(5))));
+        assert_eq!(parse("var  12 "), Ok(Query::Variable(VarId::new(12))));
+        assert_eq!(parse("var x"), Err("`x` isn't a variable id".into()));
+        assert_eq!(parse("var"), Err("`` isn't a variable id".into()));
+        assert_eq!(parse("$name"), Ok(Query::VariableName("$name".into())));
+    }
+
+    #[test]
+    fn parses_decls() {
+        assert_eq!(parse("decl str join"), Ok(Query::Decl("str join".into())));
+        assert_eq!(
+            parse("decl"),
+            Err("`decl` needs something to look for".into())
+        );
+    }
+
+    #[test]
+    fn parses_source() {
+        assert_eq!(
+            parse("src $env.PATH"),
+            Ok(Query::Source("$env.PATH".into()))
+        );
+        assert_eq!(
+            parse("src  "),
+            Err("`src` needs something to look for".into())
+        );
+    }
+}
@@ -4,7 +4,15 @@ use nu_protocol::{
     Category, Example, IntoSpanned, LabeledError, ShellError, Signature, SyntaxShape, Value,
 };
 
+mod analysis;
+mod ast;
+mod commands;
 mod data;
+mod emulator;
+mod filter;
+mod profile;
+mod pseudocode;
+mod syntax;
 mod ui;
 
 pub struct ExploreIrPlugin;
@@ -20,6 +28,14 @@ impl Plugin for ExploreIrPlugin {
         vec![
             // Commands should be added here
             Box::new(ExploreIr),
+            Box::new(commands::IrVerify),
+            Box::new(commands::IrLint),
+            Box::new(commands::IrDecompile),
+            Box::new(commands::IrAudit),
+            Box::new(commands::IrConsts),
+            Box::new(commands::IrCoverage),
+            Box::new(commands::IrEnv),
+            Box::new(commands::IrStats),
         ]
     }
 }
@@ -45,6 +61,12 @@ impl SimplePluginCommand for ExploreIr {
                 "Integer is a declaration ID rather than a block ID.",
                 Some('d'),
             )
+            .named(
+                "profile",
+                SyntaxShape::Closure(None),
+                "Run a closure under `debug profile`, and show how often each instruction ran and how long it took.",
+                Some('p'),
+            )
             .category(Category::Viewers)
     }
 
@@ -63,6 +85,11 @@ impl SimplePluginCommand for ExploreIr {
                 description: "Open a terminal viewer for the IR of the { 1 + 2 } block.",
                 result: None,
             },
+            Example {
+                example: "explore ir 'my command' --profile { my command big.csv }",
+                description: "Find the instructions in a custom command that take the most time when it's run on a file.",
+                result: None,
+            },
             Example {
                 example: "explore ir 'std bench'",
                 description: "Explore IR for the 'std bench' command. Only works for custom commands (written in Nushell).",
@@ -81,8 +108,7 @@ impl SimplePluginCommand for ExploreIr {
         let target = call.req(0)?;
         let is_decl_id = call.has_flag("decl-id")?;
 
-        let initial_block = data::get(engine, target, is_decl_id, call.head)?;
-
+        // Check before doing anything slow, like running the closure to profile
         if engine.is_using_stdio() {
             return Err(
                 LabeledError::new("Plugin can't run under stdio mode").with_label(
@@ -92,9 +118,24 @@ impl SimplePluginCommand for ExploreIr {
             );
         }
 
+        // Signatures just make the types more precise, so don't fail without them
+        let signatures = data::signatures(engine, call.head).unwrap_or_default();
+        let initial_block = data::get(engine, target, is_decl_id, &signatures, call.head)?;
+
+        let profile = call
+            .get_flag("profile")?
+            .map(|closure| profile::run(engine, closure, call.head))
+            .transpose()?;
+
         let foreground = engine.enter_foreground()?;
-        ui::start(engine.clone(), call.head, initial_block)
-            .map_err(|err| ShellError::from(err.into_spanned(call.head)))?;
+        ui::start(
+            engine.clone(),
+            call.head,
+            initial_block,
+            signatures,
+            profile,
+        )
+        .map_err(|err| ShellError::from(err.into_spanned(call.head)))?;
         drop(foreground);
 
         Ok(Value::nothing(call.head))
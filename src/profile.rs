@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{IntoSpanned, LabeledError, PipelineData, Span, Value};
+
+use crate::{
+    analysis::{Cfg, EdgeKind},
+    data::ViewIrOutput,
+};
+
+/// How deep into nested blocks and calls `debug profile` records instructions
+const MAX_DEPTH: i64 = 1000;
+
+/// One instruction that ran, from the output of `debug profile --instructions`
+#[derive(Debug, Clone)]
+pub struct ProfileRow {
+    /// The row of the instruction that called the block this one is in
+    pub parent_id: i64,
+    pub pc: usize,
+    pub instruction: String,
+    pub span: Span,
+    pub duration_ms: f64,
+}
+
+/// How often an instruction ran, and how long it took in total, including any calls it made
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InstructionProfile {
+    pub hits: u64,
+    pub duration_ms: f64,
+}
+
+/// Run a closure under `debug profile`, and get every instruction that ran in it and the blocks
+/// it called
+pub fn run(
+    engine: &EngineInterface,
+    closure: Value,
+    head: Span,
+) -> Result<Vec<ProfileRow>, LabeledError> {
+    let Some(decl_id) = engine.find_decl("debug profile")? else {
+        return Err(LabeledError::new("Can't find `debug profile`")
+            .with_label("must be in scope to profile", head));
+    };
+
+    let closure_span = closure.span();
+
+    let rows = engine
+        .call_decl(
+            decl_id,
+            EvaluatedCall::new(head)
+                .with_named("spans".into_spanned(head), Value::bool(true, head))
+                .with_named("instructions".into_spanned(head), Value::bool(true, head))
+                .with_named("max-depth".into_spanned(head), Value::int(MAX_DEPTH, head))
+                .with_positional(closure),
+            PipelineData::Empty,
+            true,
+            false,
+        )?
+        .into_value(head)?
+        .into_list()
+        .map_err(|err| {
+            LabeledError::new("Failed to read output of `debug profile`")
+                .with_label(err.to_string(), closure_span)
+        })?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let record = row.as_record().ok()?;
+            let span = record.get("span")?.as_record().ok()?;
+            let offset = |name: &str| usize::try_from(span.get(name)?.as_int().ok()?).ok();
+            Some(ProfileRow {
+                parent_id: record.get("parent_id")?.as_int().ok()?,
+                pc: usize::try_from(record.get("pc")?.as_int().ok()?).ok()?,
+                instruction: record.get("instruction")?.as_str().ok()?.to_owned(),
+                span: Span::new(offset("start")?, offset("end")?),
+                duration_ms: record.get("duration_ms")?.as_float().ok()?,
+            })
+        })
+        .collect())
+}
+
+/// Whether the ways a conditional branch could go were taken while profiling
+#[derive(Debug, Clone, Copy)]
+pub struct BranchCoverage {
+    pub index: usize,
+    /// The branch jumped to its target
+    pub taken: bool,
+    /// The branch continued with the next instruction
+    pub not_taken: bool,
+}
+
+/// Check whether a row is for an instruction in a block. The profile doesn't say which block
+/// each instruction was in, so rows are matched by their index, span and formatted instruction,
+/// which are only all the same for the same instruction.
+fn in_block(row: &ProfileRow, view_ir: &ViewIrOutput) -> bool {
+    view_ir.ir_block.spans.get(row.pc) == Some(&row.span)
+        && view_ir.formatted_instructions.get(row.pc) == Some(&row.instruction)
+}
+
+/// Add up the rows that belong to a block
+pub fn block_profile(rows: &[ProfileRow], view_ir: &ViewIrOutput) -> Vec<InstructionProfile> {
+    let mut profile = vec![InstructionProfile::default(); view_ir.ir_block.instructions.len()];
+    for row in rows.iter().filter(|row| in_block(row, view_ir)) {
+        profile[row.pc].hits += 1;
+        profile[row.pc].duration_ms += row.duration_ms;
+    }
+    profile
+}
+
+/// Find which ways each conditional branch in a block went. The instructions that ran in one
+/// call of a block are rows with the same parent, in the order they ran, so each pair of them
+/// that are next to each other is an edge that was followed.
+pub fn branch_coverage(rows: &[ProfileRow], view_ir: &ViewIrOutput) -> Vec<BranchCoverage> {
+    let cfg = Cfg::new(&view_ir.ir_block.instructions);
+    let mut branches: Vec<BranchCoverage> = (0..cfg.len())
+        .filter(|&index| {
+            let kinds = || cfg.successors[index].iter().map(|edge| edge.kind);
+            kinds().any(|kind| kind == EdgeKind::Branch)
+                && kinds().any(|kind| kind == EdgeKind::Next)
+        })
+        .map(|index| BranchCoverage {
+            index,
+            taken: false,
+            not_taken: false,
+        })
+        .collect();
+
+    // The last instruction from the block that ran under each parent
+    let mut previous: HashMap<i64, Option<usize>> = HashMap::new();
+    for row in rows {
+        let pc = in_block(row, view_ir).then_some(row.pc);
+        if let (Some(Some(from)), Some(to)) = (previous.get(&row.parent_id), pc) {
+            if let Some(branch) = branches.iter_mut().find(|branch| branch.index == *from) {
+                for edge in cfg.successors[*from]
+                    .iter()
+                    .filter(|edge| edge.target == to)
+                {
+                    match edge.kind {
+                        EdgeKind::Branch => branch.taken = true,
+                        EdgeKind::Next => branch.not_taken = true,
+                        EdgeKind::Error => (),
+                    }
+                }
+            }
+        }
+        previous.insert(row.parent_id, pc);
+    }
+    branches
+}
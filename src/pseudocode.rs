@@ -0,0 +1,974 @@
+//! Reconstruct Nushell-like code from the instructions of a block
+
+use std::{fmt::Write, ops::Range};
+
+use nu_protocol::{
+    ast::RangeInclusion,
+    ir::{Instruction, IrBlock, Literal, RedirectMode},
+    RegId, VarId,
+};
+
+use crate::{
+    analysis::{self, registers, Cfg, ErrorHandler, Loop},
+    data::ViewIrOutput,
+};
+
+/// How deep to go when reconstructing the expression that produced a register
+const MAX_DEPTH: usize = 6;
+
+pub struct Pseudocode<'a> {
+    ir_block: &'a IrBlock,
+    formatted_instructions: &'a [String],
+    cfg: Cfg,
+}
+
+impl<'a> Pseudocode<'a> {
+    pub fn new(view_ir: &'a ViewIrOutput) -> Pseudocode<'a> {
+        Pseudocode {
+            ir_block: &view_ir.ir_block,
+            formatted_instructions: &view_ir.formatted_instructions,
+            cfg: Cfg::new(&view_ir.ir_block.instructions),
+        }
+    }
+
+    /// Reconstruct the command line for the call at `index` from the instructions that set up its
+    /// arguments, like `ls --all $path`. Returns `None` if it's not a call.
+    pub fn call_line(&self, index: usize) -> Option<String> {
+        self.call_line_at(index, 0)
+    }
+
+    fn call_line_at(&self, index: usize, depth: usize) -> Option<String> {
+        let Instruction::Call { decl_id, src_dst } = self.ir_block.instructions.get(index)? else {
+            return None;
+        };
+
+        let mut parts = vec![self
+            .name(index)
+            .unwrap_or_else(|| format!("decl {}", decl_id.get()))];
+
+        // Arguments are reset by each call, so only look back as far as the previous one
+        let mut setup = vec![];
+        let mut prev = self.block_prev(index);
+        while let Some(at) = prev {
+            if matches!(self.ir_block.instructions[at], Instruction::Call { .. }) {
+                break;
+            }
+            setup.push(at);
+            prev = self.block_prev(at);
+        }
+
+        for &at in setup.iter().rev() {
+            let arg = match self.ir_block.instructions[at] {
+                Instruction::PushPositional { src } => self.expr(src, at, depth),
+                Instruction::AppendRest { src } => format!("...{}", self.expr(src, at, depth)),
+                Instruction::PushFlag { name } => format!("--{}", self.string(name)),
+                Instruction::PushShortFlag { short } => format!("-{}", self.string(short)),
+                Instruction::PushNamed { name, src } => {
+                    format!("--{} {}", self.string(name), self.expr(src, at, depth))
+                }
+                Instruction::PushShortNamed { short, src } => {
+                    format!("-{} {}", self.string(short), self.expr(src, at, depth))
+                }
+                Instruction::RedirectOut { mode } => match self.redirect(mode, at, depth) {
+                    Some(target) => format!("o>{target}"),
+                    None => continue,
+                },
+                Instruction::RedirectErr { mode } => match self.redirect(mode, at, depth) {
+                    Some(target) => format!("e>{target}"),
+                    None => continue,
+                },
+                _ => continue,
+            };
+            parts.push(arg);
+        }
+
+        let line = parts.join(" ");
+        // Only show the input if it's clear where it came from
+        match self.find_def(*src_dst, index) {
+            Some(_) => Some(format!("{} | {line}", self.expr(*src_dst, index, depth))),
+            None => Some(line),
+        }
+    }
+
+    /// The target of a redirection, after `o>` or `e>`, if it's anything other than the default
+    pub fn redirect(&self, mode: RedirectMode, at: usize, depth: usize) -> Option<String> {
+        match mode {
+            RedirectMode::Null => Some(" null".into()),
+            RedirectMode::PipeSeparate => Some("|".into()),
+            RedirectMode::File { file_num } => {
+                let mut prev = self.block_prev(at);
+                while let Some(open_at) = prev {
+                    if let Instruction::OpenFile {
+                        file_num: num,
+                        path,
+                        append,
+                    } = self.ir_block.instructions[open_at]
+                    {
+                        if num == file_num {
+                            let arrow = if append { ">" } else { "" };
+                            return Some(format!("{arrow} {}", self.expr(path, open_at, depth)));
+                        }
+                    }
+                    prev = self.block_prev(open_at);
+                }
+                Some(format!(" file({file_num})"))
+            }
+            _ => None,
+        }
+    }
+
+    /// Reconstruct the expression that was last written to `reg_id` before the instruction `at`,
+    /// within the same basic block. Falls back to the register name.
+    pub fn expr(&self, reg_id: RegId, at: usize, depth: usize) -> String {
+        match self.find_def(reg_id, at) {
+            Some(def_at) => self.def_expr(reg_id, def_at, depth),
+            None => reg_id.to_string(),
+        }
+    }
+
+    /// Reconstruct the expression for the value that the instruction at `def_at` wrote to `reg_id`
+    fn def_expr(&self, reg_id: RegId, def_at: usize, depth: usize) -> String {
+        let fallback = || reg_id.to_string();
+        if depth > MAX_DEPTH {
+            return fallback();
+        }
+        let depth = depth + 1;
+        match &self.ir_block.instructions[def_at] {
+            Instruction::LoadLiteral {
+                lit:
+                    Literal::Range {
+                        start,
+                        end,
+                        inclusion,
+                        ..
+                    },
+                ..
+            } => {
+                let op = match inclusion {
+                    RangeInclusion::Inclusive => "..",
+                    RangeInclusion::RightExclusive => "..<",
+                };
+                let start = self.expr(*start, def_at, depth);
+                let end = self.expr(*end, def_at, depth);
+                let start = if start == "null" { "" } else { &start };
+                let end = if end == "null" { "" } else { &end };
+                format!("{start}{op}{end}")
+            }
+            Instruction::LoadLiteral { lit, .. } => self.literal(lit).unwrap_or_else(fallback),
+            Instruction::LoadVariable { var_id, .. } => self
+                .name(def_at)
+                .unwrap_or_else(|| format!("$var{}", var_id.get())),
+            Instruction::LoadEnv { key, .. } => format!("$env.{}", self.string(*key)),
+            Instruction::LoadEnvOpt { key, .. } => format!("$env.{}?", self.string(*key)),
+            Instruction::Move { src, .. }
+            | Instruction::Clone { src, .. }
+            | Instruction::Span { src_dst: src }
+            | Instruction::Collect { src_dst: src }
+            | Instruction::GlobFrom { src_dst: src, .. } => self.expr(*src, def_at, depth - 1),
+            Instruction::Not { src_dst } => format!("not {}", self.expr(*src_dst, def_at, depth)),
+            Instruction::BinaryOp { lhs_dst, op, rhs } => format!(
+                "({} {op} {})",
+                self.expr(*lhs_dst, def_at, depth),
+                self.expr(*rhs, def_at, depth)
+            ),
+            Instruction::FollowCellPath { src_dst: src, path }
+            | Instruction::CloneCellPath { src, path, .. } => {
+                let path = self.expr(*path, def_at, depth);
+                format!(
+                    "{}{}",
+                    self.expr(*src, def_at, depth),
+                    path.strip_prefix('$').unwrap_or(&path)
+                )
+            }
+            Instruction::StringAppend { src_dst, val } => format!(
+                "({} ++ {})",
+                self.expr(*src_dst, def_at, depth),
+                self.expr(*val, def_at, depth)
+            ),
+            Instruction::ListPush { src_dst, item } => self.insert(
+                self.expr(*src_dst, def_at, depth),
+                ']',
+                " ",
+                &self.expr(*item, def_at, depth),
+            ),
+            Instruction::ListSpread { src_dst, items } => self.insert(
+                self.expr(*src_dst, def_at, depth),
+                ']',
+                " ",
+                &format!("...{}", self.expr(*items, def_at, depth)),
+            ),
+            Instruction::RecordInsert { src_dst, key, val } => {
+                let key = self.expr(*key, def_at, depth);
+                let key = unquote(&key).unwrap_or(&key);
+                self.insert(
+                    self.expr(*src_dst, def_at, depth),
+                    '}',
+                    ", ",
+                    &format!("{key}: {}", self.expr(*val, def_at, depth)),
+                )
+            }
+            Instruction::RecordSpread { src_dst, items } => self.insert(
+                self.expr(*src_dst, def_at, depth),
+                '}',
+                ", ",
+                &format!("...{}", self.expr(*items, def_at, depth)),
+            ),
+            Instruction::Call { .. } => self
+                .call_line_at(def_at, depth)
+                .map(|line| format!("({line})"))
+                .unwrap_or_else(fallback),
+            _ => fallback(),
+        }
+    }
+
+    /// Add an item before the closing delimiter of a list or record expression
+    fn insert(&self, collection: String, close: char, separator: &str, item: &str) -> String {
+        match collection.strip_suffix(close) {
+            Some(open) if open.len() == 1 => format!("{open}{item}{close}"),
+            Some(open) => format!("{open}{separator}{item}{close}"),
+            None => collection,
+        }
+    }
+
+    fn literal(&self, lit: &Literal) -> Option<String> {
+        Some(match lit {
+            Literal::Bool(b) => b.to_string(),
+            Literal::Int(i) => i.to_string(),
+            Literal::Float(f) => format!("{f:?}"),
+            Literal::Filesize(q) => format!("{q}b"),
+            Literal::Duration(q) => format!("{q}ns"),
+            Literal::Binary(slice) => format!(
+                "0x[{}]",
+                analysis::data_slice(self.ir_block, *slice)?.iter().fold(
+                    String::new(),
+                    |mut hex, byte| {
+                        let _ = write!(hex, "{byte:02x}");
+                        hex
+                    }
+                )
+            ),
+            Literal::Block(id) => format!("block({})", id.get()),
+            Literal::Closure(id) => format!("closure({})", id.get()),
+            Literal::RowCondition(id) => format!("row_condition({})", id.get()),
+            Literal::List { .. } => "[]".into(),
+            Literal::Record { .. } => "{}".into(),
+            Literal::Filepath { val, .. }
+            | Literal::Directory { val, .. }
+            | Literal::GlobPattern { val, .. }
+            | Literal::String(val)
+            | Literal::RawString(val) => format!("{:?}", self.string(*val)),
+            Literal::CellPath(path) => path.to_string(),
+            Literal::Date(date) => date.to_rfc3339(),
+            Literal::Nothing => "null".into(),
+            Literal::Range { .. } => return None,
+        })
+    }
+
+    /// Get a string from the block's data, like the key of an environment variable
+    pub fn string(&self, slice: nu_protocol::ir::DataSlice) -> String {
+        analysis::data_str(self.ir_block, slice)
+            .unwrap_or_default()
+            .to_owned()
+    }
+
+    /// Get the quoted name of a declaration or variable from a formatted instruction
+    pub fn name(&self, index: usize) -> Option<String> {
+        let formatted = self.formatted_instructions.get(index)?;
+        let start = formatted.find('"')?;
+        let end = formatted.rfind('"')?;
+        let quoted = formatted
+            .get(start..=end)
+            .filter(|quoted| quoted.len() > 1)?;
+        Some(serde_json::from_str(quoted).unwrap_or_else(|_| quoted[1..quoted.len() - 1].into()))
+    }
+
+    /// Get the name of a variable from the formatted instructions that use it, if it's named there
+    pub fn variable_name(&self, var_id: VarId) -> Option<String> {
+        self.ir_block
+            .instructions
+            .iter()
+            .enumerate()
+            .find_map(|(index, instruction)| match instruction {
+                Instruction::LoadVariable { var_id: id, .. }
+                | Instruction::StoreVariable { var_id: id, .. }
+                | Instruction::DropVariable { var_id: id }
+                    if *id == var_id =>
+                {
+                    self.name(index)
+                }
+                _ => None,
+            })
+    }
+
+    /// Find the last instruction before `at` in the same basic block that writes to `reg_id`, if
+    /// the value wasn't taken out of it since
+    fn find_def(&self, reg_id: RegId, at: usize) -> Option<usize> {
+        self.last_def(reg_id, self.block_prev(at)?)
+    }
+
+    /// Like [`find_def`](Self::find_def), but starting from `from` itself
+    fn last_def(&self, reg_id: RegId, from: usize) -> Option<usize> {
+        let mut prev = Some(from);
+        while let Some(index) = prev {
+            let instruction = &self.ir_block.instructions[index];
+            if instruction.output_register() == Some(reg_id) {
+                return Some(index);
+            } else if registers::takes(instruction, reg_id) {
+                return None;
+            }
+            prev = self.block_prev(index);
+        }
+        None
+    }
+
+    /// Get the instruction before `index`, if it's in the same basic block
+    fn block_prev(&self, index: usize) -> Option<usize> {
+        let prev = index.checked_sub(1)?;
+        (self.cfg.straight_line_next(prev) == Some(index)).then_some(prev)
+    }
+}
+
+/// Remove the quotes from a string that doesn't need them, like a record key
+fn unquote(string: &str) -> Option<&str> {
+    string.strip_prefix('"')?.strip_suffix('"').filter(|inner| {
+        !inner.is_empty()
+            && inner
+                .chars()
+                .all(|ch| ch.is_alphanumeric() || ch == '_' || ch == '-')
+    })
+}
+
+/// A line of decompiled code
+pub struct PseudoLine {
+    /// How many levels the line is indented
+    pub depth: usize,
+    pub text: String,
+    /// The instructions that the line was reconstructed from
+    pub instructions: Vec<usize>,
+}
+
+impl Pseudocode<'_> {
+    /// Reconstruct structured code for the whole block, like `if`/`else`, `for`, `try`/`catch`
+    /// and `match`, from the patterns that the compiler generates for them. Anything that doesn't
+    /// fit a pattern is shown as a comment with the instruction.
+    pub fn decompile(&self) -> Vec<PseudoLine> {
+        let instructions = &self.ir_block.instructions;
+        let mut decompiler = Decompiler {
+            code: self,
+            loops: analysis::loops(instructions),
+            handlers: analysis::error_handlers(instructions),
+            live_out: registers::live_out(
+                instructions,
+                &self.cfg,
+                registers::uses,
+                self.ir_block.register_count,
+            ),
+            reachable: self.cfg.reachable(),
+            shown: vec![false; instructions.len()],
+            lines: vec![],
+            pending: vec![],
+        };
+        decompiler.region(0..instructions.len(), 0, None);
+
+        // Anything left over belongs to the last line
+        let pending = std::mem::take(&mut decompiler.pending);
+        match decompiler.lines.last_mut() {
+            Some(line) => line.instructions.extend(pending),
+            None if !pending.is_empty() => decompiler.line(0, String::new()),
+            None => (),
+        }
+        decompiler.lines
+    }
+}
+
+#[derive(Clone, Copy)]
+struct LoopContext {
+    header: usize,
+    exit: usize,
+}
+
+struct Decompiler<'a, 'b> {
+    code: &'b Pseudocode<'a>,
+    loops: Vec<Loop>,
+    handlers: Vec<Vec<ErrorHandler>>,
+    live_out: Vec<Vec<bool>>,
+    reachable: Vec<bool>,
+    /// Instructions whose values have already been shown in a line
+    shown: Vec<bool>,
+    lines: Vec<PseudoLine>,
+    /// Instructions that have been visited but not added to a line yet
+    pending: Vec<usize>,
+}
+
+impl Decompiler<'_, '_> {
+    fn instruction(&self, index: usize) -> &Instruction {
+        &self.code.ir_block.instructions[index]
+    }
+
+    fn line(&mut self, depth: usize, text: String) {
+        self.lines.push(PseudoLine {
+            depth,
+            text,
+            instructions: std::mem::take(&mut self.pending),
+        });
+    }
+
+    /// Add a comment line for an instruction that doesn't fit any pattern
+    fn fallback(&mut self, index: usize, depth: usize) {
+        let formatted = &self.code.formatted_instructions[index];
+        self.line(depth, format!("# {index}: {}", formatted.trim_end()));
+    }
+
+    /// The expression for a value that's used as a statement, unless there's nothing to show
+    fn value(&mut self, reg_id: RegId, at: usize) -> Option<String> {
+        let def_at = self.code.find_def(reg_id, at)?;
+        self.statement(reg_id, def_at).filter(|text| text != "null")
+    }
+
+    /// The expression for the value written by an instruction, without parentheses around a call,
+    /// unless it has been shown already
+    fn statement(&mut self, reg_id: RegId, def_at: usize) -> Option<String> {
+        if std::mem::replace(&mut self.shown[def_at], true) {
+            return None;
+        }
+        let text = match self.instruction(def_at) {
+            Instruction::Call { .. } => self.code.call_line(def_at)?,
+            _ => self.code.def_expr(reg_id, def_at, 0),
+        };
+        (text != reg_id.to_string()).then_some(text)
+    }
+
+    /// The expression for a value that's assigned to something by the instruction at `at`
+    fn assigned(&mut self, reg_id: RegId, at: usize) -> String {
+        self.code
+            .find_def(reg_id, at)
+            .and_then(|def_at| self.statement(reg_id, def_at))
+            .unwrap_or_else(|| self.code.expr(reg_id, at, 0))
+    }
+
+    /// Get the name of the variable stored to or loaded from by an instruction
+    fn var_name(&self, index: usize) -> String {
+        match self.code.name(index) {
+            Some(name) => name,
+            None => match self.instruction(index) {
+                Instruction::StoreVariable { var_id, .. }
+                | Instruction::LoadVariable { var_id, .. } => format!("$var{}", var_id.get()),
+                _ => "$_".into(),
+            },
+        }
+    }
+
+    fn region(&mut self, range: Range<usize>, depth: usize, in_loop: Option<LoopContext>) {
+        let mut index = range.start;
+        while index < range.end {
+            if let Some(next) = self.try_loop(index, range.end, depth, in_loop) {
+                index = next;
+                continue;
+            }
+
+            self.pending.push(index);
+            // Code that can never run isn't shown
+            if !self.reachable[index] {
+                index += 1;
+                continue;
+            }
+            let next = match *self.instruction(index) {
+                Instruction::BranchIf {
+                    cond,
+                    index: target,
+                } if target > index && target <= range.end => {
+                    Some(self.if_else(index, cond, target, range.end, depth, in_loop))
+                }
+                Instruction::OnError { .. } | Instruction::OnErrorInto { .. } => {
+                    self.try_catch(index, range.end, depth, in_loop)
+                }
+                Instruction::Match { .. } => self.match_arms(index, range.end, depth, in_loop),
+                _ => None,
+            };
+            if let Some(next) = next {
+                index = next;
+                continue;
+            }
+
+            match *self.instruction(index) {
+                Instruction::Jump { index: target } => match in_loop {
+                    Some(context) if target == context.header => {
+                        self.line(depth, "continue".into())
+                    }
+                    Some(context) if target == context.exit => self.line(depth, "break".into()),
+                    // Jumping to the end of the region is implied
+                    _ if target == range.end => (),
+                    _ => self.fallback(index, depth),
+                },
+                Instruction::StoreVariable { src, .. } => {
+                    let name = self.var_name(index);
+                    let text = format!("{name} = {}", self.assigned(src, index));
+                    self.line(depth, text);
+                }
+                Instruction::StoreEnv { key, src } => {
+                    let text = format!(
+                        "$env.{} = {}",
+                        self.code.string(key),
+                        self.assigned(src, index)
+                    );
+                    self.line(depth, text);
+                }
+                Instruction::Drop { src }
+                | Instruction::Drain { src }
+                | Instruction::DrainIfEnd { src } => {
+                    if let Some(text) = self.value(src, index) {
+                        self.line(depth, text);
+                    }
+                }
+                Instruction::Return { src } => {
+                    if let Some(text) = self.value(src, index) {
+                        self.line(depth, text);
+                    }
+                }
+                Instruction::ReturnEarly { src } => {
+                    let text = match self.value(src, index) {
+                        Some(text) => format!("return {text}"),
+                        None => "return".into(),
+                    };
+                    self.line(depth, text);
+                }
+                Instruction::BranchIf { .. }
+                | Instruction::BranchIfEmpty { .. }
+                | Instruction::Match { .. }
+                | Instruction::Iterate { .. }
+                | Instruction::OnError { .. }
+                | Instruction::OnErrorInto { .. }
+                | Instruction::PopErrorHandler
+                | Instruction::Unreachable => self.fallback(index, depth),
+                // Everything else is part of the expression on a later line
+                _ => (),
+            }
+            index += 1;
+        }
+    }
+
+    /// Add assignments for the values that a branch leaves in registers for after it ends, like
+    /// the value of an `if` expression
+    fn region_values(&mut self, range: Range<usize>, depth: usize) {
+        let Some(last) = range.end.checked_sub(1).filter(|last| *last >= range.start) else {
+            return;
+        };
+        for (reg, live) in self.live_out[last].clone().into_iter().enumerate() {
+            let reg_id = RegId::new(reg as u32);
+            if let Some(def_at) = self
+                .code
+                .last_def(reg_id, last)
+                .filter(|def_at| live && range.contains(def_at))
+            {
+                if let Some(text) = self.statement(reg_id, def_at) {
+                    self.line(depth, format!("{reg_id} = {text}"));
+                }
+            }
+        }
+    }
+
+    /// Add a nested region inside of a block, removing the line that opened the block if the region
+    /// turns out to be empty
+    fn nested(
+        &mut self,
+        opening: String,
+        range: Range<usize>,
+        depth: usize,
+        in_loop: Option<LoopContext>,
+    ) {
+        let before = self.lines.len();
+        self.line(depth, opening);
+        self.region(range.clone(), depth + 1, in_loop);
+        self.region_values(range, depth + 1);
+        if self.lines.len() == before + 1 {
+            let opening = self.lines.pop().expect("just pushed");
+            self.pending.extend(opening.instructions);
+        }
+    }
+
+    fn if_else(
+        &mut self,
+        index: usize,
+        cond: RegId,
+        target: usize,
+        end: usize,
+        depth: usize,
+        in_loop: Option<LoopContext>,
+    ) -> usize {
+        let jump_at = |at: usize| match self.code.ir_block.instructions.get(at) {
+            Some(Instruction::Jump { index }) => Some(*index),
+            _ => None,
+        };
+        // Find the instructions that run when the condition is true and when it's false. The
+        // branch that comes first jumps over the other one to where they join.
+        let (then_range, else_range, jumps, next) = match jump_at(index + 1) {
+            Some(else_start)
+                if target == index + 2 && else_start >= target && else_start <= end =>
+            {
+                match jump_at(else_start - 1) {
+                    Some(join) if else_start > target && join >= else_start && join <= end => (
+                        target..else_start - 1,
+                        else_start..join,
+                        vec![index + 1, else_start - 1],
+                        join,
+                    ),
+                    _ => (
+                        target..else_start,
+                        else_start..else_start,
+                        vec![index + 1],
+                        else_start,
+                    ),
+                }
+            }
+            _ => match jump_at(target - 1) {
+                Some(join) if target - 1 > index && join >= target && join <= end => {
+                    (target..join, index + 1..target - 1, vec![target - 1], join)
+                }
+                _ => (target..target, index + 1..target, vec![], target),
+            },
+        };
+
+        let cond = self.code.expr(cond, index, 0);
+        let (cond, first, second) = match cond.strip_prefix("not ") {
+            Some(inner) => (inner.to_owned(), else_range, then_range),
+            None if then_range.is_empty() => (format!("not {cond}"), else_range, then_range),
+            None => (cond, then_range, else_range),
+        };
+
+        self.line(depth, format!("if {cond} {{"));
+        self.region(first.clone(), depth + 1, in_loop);
+        self.region_values(first, depth + 1);
+        self.pending.extend(jumps);
+        self.nested("} else {".into(), second, depth, in_loop);
+        self.line(depth, "}".into());
+        next
+    }
+
+    fn try_loop(
+        &mut self,
+        index: usize,
+        end: usize,
+        depth: usize,
+        in_loop: Option<LoopContext>,
+    ) -> Option<usize> {
+        if in_loop.is_some_and(|context| context.header == index) {
+            return None;
+        }
+        let the_loop = self.loops.iter().find(|l| l.header == index)?;
+        let back = *the_loop.body.last()?;
+        if back >= end {
+            return None;
+        }
+        // A loop without `Iterate` exits by jumping out from somewhere in its body, maybe through
+        // another jump
+        let exit = the_loop.exit.unwrap_or_else(|| {
+            the_loop
+                .body
+                .iter()
+                .flat_map(|at| &self.code.cfg.successors[*at])
+                .filter(|edge| the_loop.body.binary_search(&edge.target).is_err())
+                .map(|edge| match *self.instruction(edge.target) {
+                    Instruction::Jump { index } => index,
+                    _ => edge.target,
+                })
+                .filter(|target| *target > back && *target <= end)
+                .max()
+                .unwrap_or(back + 1)
+        });
+        let context = LoopContext {
+            header: index,
+            exit,
+        };
+        // Unreachable code at the end of the loop is part of it too
+        let back = (back + 1..exit)
+            .take_while(|at| self.reachable.get(*at) == Some(&false))
+            .last()
+            .unwrap_or(back);
+        // The jump back to the start is implied
+        let body_end = match *self.instruction(back) {
+            Instruction::Jump { index: target } if target == index => back,
+            _ => back + 1,
+        };
+
+        match *self.instruction(index) {
+            Instruction::Iterate { dst, stream, .. } => {
+                self.pending.push(index);
+                let mut body_start = index + 1;
+                let mut name = dst.to_string();
+                if let Some(Instruction::StoreVariable { src, .. }) =
+                    self.code.ir_block.instructions.get(index + 1)
+                {
+                    if *src == dst {
+                        name = self.var_name(index + 1);
+                        self.pending.push(index + 1);
+                        body_start += 1;
+                    }
+                }
+                // The stream comes from before the loop, but the jump back also leads here
+                let stream = index
+                    .checked_sub(1)
+                    .filter(|prev| {
+                        matches!(self.code.cfg.successors[*prev][..], [edge] if edge.target == index)
+                    })
+                    .and_then(|prev| self.code.last_def(stream, prev))
+                    .map(|def_at| self.code.def_expr(stream, def_at, 0))
+                    .unwrap_or_else(|| stream.to_string());
+                self.line(depth, format!("for {name} in {stream} {{"));
+                self.region(body_start..body_end, depth + 1, Some(context));
+            }
+            _ => {
+                // A while loop checks its condition at the start and jumps out if it's false
+                let mut check = Some(index);
+                while let Some(at) = check {
+                    match *self.instruction(at) {
+                        Instruction::BranchIf {
+                            cond,
+                            index: target,
+                        } if target == at + 2
+                            && matches!(
+                                self.code.ir_block.instructions.get(at + 1),
+                                Some(Instruction::Jump { index: exit }) if *exit == context.exit
+                            ) =>
+                        {
+                            self.pending.extend(index..target);
+                            let cond = self.code.expr(cond, at, 0);
+                            self.line(depth, format!("while {cond} {{"));
+                            self.region(target..body_end, depth + 1, Some(context));
+                            break;
+                        }
+                        _ => check = self.code.cfg.straight_line_next(at),
+                    }
+                }
+                if check.is_none() {
+                    self.line(depth, "loop {".into());
+                    self.region(index..body_end, depth + 1, Some(context));
+                }
+            }
+        }
+
+        self.pending.extend(body_end..back + 1);
+        self.line(depth, "}".into());
+        Some(back + 1)
+    }
+
+    fn try_catch(
+        &mut self,
+        index: usize,
+        end: usize,
+        depth: usize,
+        in_loop: Option<LoopContext>,
+    ) -> Option<usize> {
+        let (handler, dst) = match *self.instruction(index) {
+            Instruction::OnError { index: handler } => (handler, None),
+            Instruction::OnErrorInto {
+                index: handler,
+                dst,
+            } => (handler, Some(dst)),
+            _ => return None,
+        };
+        // The try block pops the handler and jumps over it
+        let pop = handler.checked_sub(2).filter(|pop| *pop > index)?;
+        if !matches!(self.instruction(pop), Instruction::PopErrorHandler)
+            || self.handlers[pop].last().map(|h| h.pushed_at) != Some(index)
+        {
+            return None;
+        }
+        let Instruction::Jump { index: join } = *self.instruction(pop + 1) else {
+            return None;
+        };
+        if join < handler || join > end {
+            return None;
+        }
+
+        self.line(depth, "try {".into());
+        self.region(index + 1..pop, depth + 1, in_loop);
+        self.region_values(index + 1..pop, depth + 1);
+        self.pending.extend([pop, pop + 1]);
+
+        // The error is stored in a variable if the catch block has a parameter
+        let mut catch_start = handler;
+        let mut opening = "} catch {".to_owned();
+        if let (
+            Some(dst),
+            Some(Instruction::Clone { dst: copy, src }),
+            Some(Instruction::StoreVariable { src: stored, .. }),
+        ) = (
+            dst,
+            self.code.ir_block.instructions.get(handler),
+            self.code.ir_block.instructions.get(handler + 1),
+        ) {
+            if *src == dst && stored == copy && handler + 1 < join {
+                let name = self.var_name(handler + 1);
+                opening = format!("}} catch {{ |{}|", name.trim_start_matches('$'));
+                self.pending.extend([handler, handler + 1]);
+                catch_start += 2;
+            }
+        }
+        self.nested(opening, catch_start..join, depth, in_loop);
+        self.line(depth, "}".into());
+        Some(join)
+    }
+
+    fn match_arms(
+        &mut self,
+        index: usize,
+        end: usize,
+        depth: usize,
+        in_loop: Option<LoopContext>,
+    ) -> Option<usize> {
+        let Instruction::Match { src, .. } = *self.instruction(index) else {
+            return None;
+        };
+
+        // Each arm is a match instruction, followed by what to do if nothing matched
+        let mut arms = vec![];
+        let mut at = index;
+        while let Some(&Instruction::Match { index: target, .. }) =
+            self.code.ir_block.instructions.get(at).filter(|_| at < end)
+        {
+            let pattern = self.pattern(at)?;
+            arms.push((at, pattern, target));
+            at += 1;
+        }
+        if at >= end {
+            return None;
+        }
+        let mut jump = at;
+        while !matches!(self.instruction(jump), Instruction::Jump { .. }) {
+            jump = self.code.cfg.straight_line_next(jump)?;
+        }
+        let Instruction::Jump { index: join } = *self.instruction(jump) else {
+            return None;
+        };
+        let targets: Vec<usize> = arms.iter().map(|(_, _, target)| *target).collect();
+        if join > end
+            || targets.windows(2).any(|pair| pair[0] >= pair[1])
+            || targets.first().is_some_and(|first| *first <= jump)
+            || targets.last().is_some_and(|last| *last >= join)
+        {
+            return None;
+        }
+
+        let subject = self.code.expr(src, index, 0);
+        self.line(depth, format!("match {subject} {{"));
+
+        for (arm, (arm_at, pattern, target)) in arms.iter().enumerate() {
+            if *arm_at != index {
+                self.pending.push(*arm_at);
+            }
+            let arm_end = targets.get(arm + 1).copied().unwrap_or(join);
+            let mut body = *target..arm_end;
+            // Each arm jumps to the end when it's done
+            if matches!(
+                self.instruction(arm_end - 1),
+                Instruction::Jump { index } if *index == join
+            ) {
+                body.end -= 1;
+            }
+            // A guard is checked first, and goes on to the next pattern if it's false
+            let mut opening = format!("{pattern} => {{");
+            let mut guard_at = Some(*target);
+            while let Some(at) = guard_at.filter(|at| *at < body.end) {
+                if let Instruction::CheckMatchGuard { src: guard } = *self.instruction(at) {
+                    if let (Some(Instruction::Not { .. }), Some(Instruction::BranchIf { .. })) = (
+                        self.code.ir_block.instructions.get(at + 1),
+                        self.code.ir_block.instructions.get(at + 2),
+                    ) {
+                        let guard = self.code.expr(guard, at, 0);
+                        opening = format!("{pattern} if {guard} => {{");
+                        self.pending.extend(body.start..at + 3);
+                        body.start = at + 3;
+                    }
+                    break;
+                }
+                guard_at = self.code.cfg.straight_line_next(at);
+            }
+            self.line(depth + 1, opening);
+            self.region(body.clone(), depth + 2, in_loop);
+            self.region_values(body.clone(), depth + 2);
+            self.pending.extend(body.end..arm_end);
+            self.line(depth + 1, "}".into());
+        }
+
+        // What happens if nothing matched
+        self.pending.extend(at..=jump);
+        self.line(depth, "}".into());
+        Some(join)
+    }
+
+    /// Get the pattern of a match instruction from how it was formatted, like `match (1), %1, 8`
+    fn pattern(&self, index: usize) -> Option<String> {
+        let formatted = self.code.formatted_instructions.get(index)?;
+        let operands = formatted.trim_start().strip_prefix("match")?.trim_start();
+        let mut parts = operands.rsplitn(3, ", ");
+        let (_target, _src, pattern) = (parts.next()?, parts.next()?, parts.next()?);
+        let pattern = pattern.strip_prefix('(')?.strip_suffix(')')?;
+        Some(pattern.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nu_protocol::{ir::Instruction, ir::Literal, RegId};
+
+    use super::*;
+    use crate::analysis::test_util::{ir_block, view_ir};
+
+    #[test]
+    fn decompiles_loop_exiting_past_the_end() {
+        // The `Iterate` ends past the last instruction, and everything after the loop is
+        // unreachable
+        let view_ir = view_ir(ir_block(vec![
+            Instruction::LoadLiteral {
+                dst: RegId::new(1),
+                lit: Literal::List { capacity: 0 },
+            },
+            Instruction::Iterate {
+                dst: RegId::new(0),
+                stream: RegId::new(1),
+                end_index: 9,
+            },
+            Instruction::Drain { src: RegId::new(0) },
+            Instruction::Jump { index: 1 },
+            Instruction::Unreachable,
+            Instruction::Unreachable,
+        ]));
+        let lines = Pseudocode::new(&view_ir).decompile();
+        assert!(lines.iter().any(|line| line.text.starts_with("for ")));
+    }
+
+    #[test]
+    fn decompiles_if_else() {
+        let view_ir = view_ir(ir_block(vec![
+            Instruction::BranchIf {
+                cond: RegId::new(0),
+                index: 3,
+            },
+            Instruction::LoadLiteral {
+                dst: RegId::new(1),
+                lit: Literal::Int(2),
+            },
+            Instruction::Jump { index: 4 },
+            Instruction::LoadLiteral {
+                dst: RegId::new(1),
+                lit: Literal::Int(1),
+            },
+            Instruction::Return { src: RegId::new(1) },
+        ]));
+        let lines: Vec<_> = Pseudocode::new(&view_ir)
+            .decompile()
+            .into_iter()
+            .map(|line| (line.depth, line.text))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (0, "if %0 {".into()),
+                (1, "%1 = 1".into()),
+                (0, "} else {".into()),
+                (1, "%1 = 2".into()),
+                (0, "}".into()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use std::{collections::HashMap, ops::Range};
+
+use nu_plugin::{EngineInterface, EvaluatedCall};
+use nu_protocol::{IntoSpanned, LabeledError, PipelineData, Span, Value};
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct FlatShape {
+    shape: String,
+    span: FlatSpan,
+}
+
+#[derive(Deserialize)]
+struct FlatSpan {
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+/// Get the shapes of `source` as Nushell would highlight it, with the ranges relative to the start
+/// of `source`
+fn flatten(
+    engine: &EngineInterface,
+    source: &str,
+    head: Span,
+) -> Result<Vec<(Range<usize>, String)>, LabeledError> {
+    let Some(decl_id) = engine.find_decl("ast")? else {
+        return Ok(vec![]);
+    };
+
+    let result = engine.call_decl(
+        decl_id,
+        EvaluatedCall::new(head)
+            .with_named("json".into_spanned(head), Value::bool(true, head))
+            .with_named("flatten".into_spanned(head), Value::bool(true, head))
+            .with_named("minify".into_spanned(head), Value::bool(true, head))
+            .with_positional(Value::string(source, head)),
+        PipelineData::Empty,
+        true,
+        false,
+    )?;
+
+    let json = result.into_value(head)?.into_string()?;
+
+    let shapes: Vec<FlatShape> = serde_json::from_str(&json).map_err(|err| {
+        LabeledError::new("Failed to parse output of `ast`").with_label(err.to_string(), head)
+    })?;
+
+    Ok(shapes
+        .into_iter()
+        .filter_map(|flat| Some((flat.span.start?..flat.span.end?, flat.shape)))
+        .collect())
+}
+
+/// Style `source` using the shape colors from the user's `color_config`
+pub fn highlight(
+    engine: &EngineInterface,
+    source: &str,
+    head: Span,
+) -> Result<Vec<(Range<usize>, Style)>, LabeledError> {
+    let config = engine.get_config()?;
+    let mut styles = HashMap::new();
+
+    Ok(flatten(engine, source, head)?
+        .into_iter()
+        .filter_map(|(range, shape)| {
+            let style = *styles
+                .entry(shape)
+                .or_insert_with_key(|shape| config.color_config.get(shape).and_then(parse_style));
+            Some((range, style?))
+        })
+        .collect())
+}
+
+/// Parse a `color_config` entry, which can be a color name, a hex color, or a record with `fg`,
+/// `bg` and `attr`
+fn parse_style(value: &Value) -> Option<Style> {
+    match value {
+        Value::String { val, .. } if val.starts_with('{') => {
+            let record: HashMap<String, String> = serde_json::from_str(val).ok()?;
+            Some(style_from_parts(
+                record.get("fg").map(|s| s.as_str()),
+                record.get("bg").map(|s| s.as_str()),
+                record.get("attr").map(|s| s.as_str()),
+            ))
+        }
+        Value::String { val, .. } => parse_named_style(val),
+        Value::Record { val, .. } => {
+            let field = |name: &str| val.get(name).and_then(|value| value.as_str().ok());
+            Some(style_from_parts(field("fg"), field("bg"), field("attr")))
+        }
+        _ => None,
+    }
+}
+
+fn style_from_parts(fg: Option<&str>, bg: Option<&str>, attr: Option<&str>) -> Style {
+    let mut style = Style::new();
+    if let Some(fg) = fg.and_then(parse_color) {
+        style = style.fg(fg);
+    }
+    if let Some(bg) = bg.and_then(parse_color) {
+        style = style.bg(bg);
+    }
+    for ch in attr.unwrap_or_default().chars() {
+        style = style.add_modifier(match ch {
+            'b' => Modifier::BOLD,
+            'd' => Modifier::DIM,
+            'i' => Modifier::ITALIC,
+            'u' => Modifier::UNDERLINED,
+            'l' => Modifier::SLOW_BLINK,
+            'r' => Modifier::REVERSED,
+            'h' => Modifier::HIDDEN,
+            's' => Modifier::CROSSED_OUT,
+            _ => Modifier::empty(),
+        });
+    }
+    style
+}
+
+/// Parse a style like `green`, `light_blue_bold`, `gb` or `#ff8800`
+fn parse_named_style(name: &str) -> Option<Style> {
+    const SUFFIXES: &[(&str, Modifier)] = &[
+        ("_bold", Modifier::BOLD),
+        ("_dimmed", Modifier::DIM),
+        ("_italic", Modifier::ITALIC),
+        ("_underline", Modifier::UNDERLINED),
+        ("_blink", Modifier::SLOW_BLINK),
+        ("_reverse", Modifier::REVERSED),
+        ("_strike", Modifier::CROSSED_OUT),
+    ];
+
+    let name = name.trim();
+    if let Some(color) = parse_color(name) {
+        return Some(Style::new().fg(color));
+    }
+
+    for (suffix, modifier) in SUFFIXES {
+        if let Some(color) = name.strip_suffix(suffix).and_then(parse_color) {
+            return Some(Style::new().fg(color).add_modifier(*modifier));
+        }
+    }
+
+    // Abbreviations, like `gb` for green bold or `lub` for light blue bold
+    (1..name.len()).rev().find_map(|i| {
+        let (color, attr) = (name.get(..i)?, name.get(i..)?);
+        if attr.chars().all(|ch| "bdiulrhs".contains(ch)) {
+            Some(style_from_parts(None, None, Some(attr)).fg(parse_color(color)?))
+        } else {
+            None
+        }
+    })
+}
+
+fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+
+    Some(match name {
+        "black" | "b" => Color::Black,
+        "red" | "r" => Color::Red,
+        "green" | "g" => Color::Green,
+        "yellow" | "y" => Color::Yellow,
+        "blue" | "u" => Color::Blue,
+        "purple" | "magenta" | "p" | "m" => Color::Magenta,
+        "cyan" | "c" => Color::Cyan,
+        "white" | "w" => Color::Gray,
+        "dark_gray" | "dgr" => Color::DarkGray,
+        "light_red" | "lr" => Color::LightRed,
+        "light_green" | "lg" => Color::LightGreen,
+        "light_yellow" | "ly" => Color::LightYellow,
+        "light_blue" | "lu" => Color::LightBlue,
+        "light_purple" | "light_magenta" | "lp" | "lm" => Color::LightMagenta,
+        "light_cyan" | "lc" => Color::LightCyan,
+        "light_gray" | "lgr" => Color::White,
+        "default" | "reset" => Color::Reset,
+        _ => return None,
+    })
+}
@@ -1,12 +1,15 @@
 use std::{
     io::{self, stdout},
+    ops::Range,
+    path::Path,
+    process::Command,
     time::Duration,
 };
 
 use nu_plugin::EngineInterface;
 use nu_protocol::{
     ir::{Instruction, Literal},
-    Value,
+    RegId, Value,
 };
 use ratatui::{
     crossterm::{
@@ -18,18 +21,54 @@ use ratatui::{
     widgets::*,
 };
 
-use crate::data::{self, BlockState, ViewIrOutput};
+use crate::{
+    analysis::{
+        self, registers, Cfg, EnvAccessKind, ErrorHandler, Provenance, Signatures, Source, Ssa,
+        SsaValue, TypeSet,
+    },
+    ast::{self, AstNode},
+    data::{self, BlockState, Filter, Fold},
+    emulator::{Argument, Emulator, Status},
+    filter,
+    profile::{self, ProfileRow},
+    pseudocode::Pseudocode,
+};
 
 struct State {
     engine: EngineInterface,
     head: nu_protocol::Span,
     blocks: Vec<BlockState>,
-    inst_list: List<'static>,
+    /// The input and output types of every command, for inferring types in each block entered
+    signatures: Signatures,
+    /// The instructions that ran while profiling, which are matched up with each block entered
+    profile: Option<Vec<ProfileRow>>,
+    inst_lines: Vec<Text<'static>>,
     jump_list: Vec<JumpState>,
+    focus: Focus,
     should_quit: bool,
+    open_editor: bool,
+    show_ast: bool,
+    show_loops: bool,
+    show_histogram: bool,
+    /// Count opcodes across every block reachable from the current one in the histogram
+    histogram_reachable: bool,
+    /// The opcode under the cursor in the histogram
+    histogram_cursor: usize,
+    show_pseudocode: bool,
+    show_ssa: bool,
+    show_types: bool,
     show_inspector: bool,
+    show_provenance: bool,
+    provenance_scroll: u16,
+    show_env: bool,
+    show_help: bool,
+    /// The variable under the cursor in the environment variable list
+    env_cursor: usize,
     goto: bool,
     goto_contents: String,
+    /// The query being typed into the filter prompt, while it's open
+    filter_prompt: bool,
+    filter_contents: String,
     error: Option<String>,
 }
 
@@ -56,10 +95,27 @@ enum JumpState {
     Goto { previous: usize },
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Instructions,
+    Source,
+    Loops,
+    Histogram,
+}
+
+enum CursorMovement {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
 pub(crate) fn start(
     engine: EngineInterface,
     head: nu_protocol::Span,
     initial_block: BlockState,
+    signatures: Signatures,
+    profile: Option<Vec<ProfileRow>>,
 ) -> io::Result<()> {
     enable_raw_mode()?;
     stdout().execute(EnterAlternateScreen)?;
@@ -70,12 +126,31 @@ pub(crate) fn start(
         engine,
         head,
         blocks: vec![],
-        inst_list: List::default(),
+        signatures,
+        profile,
+        inst_lines: vec![],
         jump_list: vec![],
+        focus: Focus::Instructions,
         should_quit: false,
+        open_editor: false,
+        show_ast: false,
+        show_loops: false,
+        show_histogram: false,
+        histogram_reachable: false,
+        histogram_cursor: 0,
+        show_pseudocode: false,
+        show_ssa: false,
+        show_types: false,
         show_inspector: false,
+        show_provenance: false,
+        provenance_scroll: 0,
+        show_env: false,
+        show_help: false,
+        env_cursor: 0,
         goto: false,
         goto_contents: String::new(),
+        filter_prompt: false,
+        filter_contents: String::new(),
         error: None,
     };
 
@@ -86,6 +161,11 @@ pub(crate) fn start(
     while !state.should_quit {
         result = result.and(terminal.draw(|frame| ui(frame, &mut state)).map(|_| ()));
         result = result.and(handle_events(&mut state));
+
+        if state.open_editor {
+            state.open_editor = false;
+            result = result.and(suspend_for_editor(&mut terminal, &mut state));
+        }
     }
 
     disable_raw_mode()
@@ -93,17 +173,141 @@ pub(crate) fn start(
         .and(result)
 }
 
-fn enter_block(state: &mut State, block: BlockState) {
+/// Leave the alternate screen while the editor runs, and come back to the UI afterward
+fn suspend_for_editor(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    state: &mut State,
+) -> io::Result<()> {
+    disable_raw_mode()?;
+    stdout().execute(LeaveAlternateScreen)?;
+
+    if let Err(err) = open_editor(state) {
+        state.error = Some(err);
+    }
+
+    enable_raw_mode()?;
+    stdout().execute(EnterAlternateScreen)?;
+    terminal.clear()
+}
+
+/// Launch `$env.EDITOR` at the location of the selected instruction (or the source cursor)
+fn open_editor(state: &State) -> Result<(), String> {
+    let block = state.current_block();
+
+    let Some(file) = &block.source_file else {
+        return Err("source file is not available".into());
+    };
+
+    // The editor runs in the engine's directory, so relative names are relative to that
+    let cwd = state.engine.get_current_dir().ok();
+    let path = match &cwd {
+        Some(cwd) => Path::new(cwd).join(&file.name),
+        None => Path::new(&file.name).to_path_buf(),
+    };
+    if !path.is_file() {
+        return Err(format!("{} is not a file", file.name));
+    }
+
+    let offset = match state.focus {
+        Focus::Source => Some(block.source_cursor),
+        Focus::Instructions | Focus::Loops | Focus::Histogram => {
+            block.view_ir.span.and_then(|block_span| {
+                block
+                    .list_state
+                    .selected()
+                    .and_then(|index| block.view_ir.ir_block.spans.get(index))
+                    .and_then(|span| span.start.checked_sub(block_span.start))
+            })
+        }
+    };
+
+    let Some((line, column)) = offset.and_then(|offset| block.source_location(offset)) else {
+        return Err("can't find the location of the selected instruction".into());
+    };
+
+    let editor = state
+        .engine
+        .get_env_var("EDITOR")
+        .map_err(|err| err.to_string())?
+        .ok_or("$env.EDITOR is not set")?
+        .coerce_into_string()
+        .map_err(|err| err.to_string())?;
+
+    // The editor may have arguments of its own, like `code --wait`
+    let mut words = editor.split_whitespace();
+    let Some(program) = words.next() else {
+        return Err("$env.EDITOR is empty".into());
+    };
+
+    let mut command = Command::new(program);
+    command.args(words);
+
+    // Editors don't agree on how to open a file at a specific line
+    match Path::new(program)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or(program)
+    {
+        "code" | "codium" | "code-insiders" => {
+            command
+                .arg("--goto")
+                .arg(format!("{}:{line}:{column}", file.name));
+        }
+        "hx" | "helix" | "zed" | "subl" => {
+            command.arg(format!("{}:{line}:{column}", file.name));
+        }
+        _ => {
+            command.arg(format!("+{line}")).arg(&file.name);
+        }
+    }
+
+    if let Some(cwd) = cwd {
+        command.current_dir(cwd);
+    }
+
+    let status = command
+        .status()
+        .map_err(|err| format!("failed to run {program}: {err}"))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{program} exited with {status}"))
+    }
+}
+
+fn enter_block(state: &mut State, mut block: BlockState) {
+    if let Some(rows) = &state.profile {
+        block.profile = profile::block_profile(rows, &block.view_ir);
+        block.branches = profile::branch_coverage(rows, &block.view_ir);
+    }
     state.blocks.push(block);
     restore_block_state(state);
+    if state.show_ast {
+        load_ast(state);
+    }
+}
+
+fn load_ast(state: &mut State) {
+    let Some(block) = state.blocks.last_mut() else {
+        return;
+    };
+    if block.ast.is_none() {
+        block.ast = Some(if block.view_ir.span.is_some() {
+            ast::parse(&state.engine, &block.source, state.head).map_err(|err| err.to_string())
+        } else {
+            Err("source code is not available".into())
+        });
+    }
 }
 
 fn restore_block_state(state: &mut State) {
     if let Some(block) = state.blocks.last() {
-        state.inst_list = make_instruction_list(&block.view_ir);
+        state.inst_lines = make_instruction_list(block, state.show_ssa, state.show_types);
     } else {
-        state.inst_list = List::default();
+        state.inst_lines = vec![];
     }
+    state.focus = Focus::Instructions;
 }
 
 fn go_forward(state: &mut State) {
@@ -129,6 +333,7 @@ fn go_forward(state: &mut State) {
                         state.head,
                     ),
                     true,
+                    &state.signatures,
                     state.head,
                 )
                 .map_err(|err| err.to_string())?;
@@ -151,6 +356,7 @@ fn go_forward(state: &mut State) {
                         state.head,
                     ),
                     false,
+                    &state.signatures,
                     state.head,
                 )
                 .map_err(|err| err.to_string())?;
@@ -173,6 +379,53 @@ fn go_forward(state: &mut State) {
     }
 }
 
+/// Show only the instructions that use the environment variable under the cursor in the
+/// environment variable list
+fn filter_env_var(state: &mut State) {
+    let cursor = state.env_cursor;
+    let block = state.current_block_mut();
+    let accesses = analysis::env_accesses(&block.view_ir.ir_block);
+    let usage = analysis::env_usage(&accesses);
+    let Some((name, uses)) = usage.get(cursor) else {
+        return;
+    };
+    let indices: Vec<usize> = uses.iter().map(|access| access.index).collect();
+    block.list_state.select(indices.first().copied());
+    block.filter = Some(Filter {
+        description: if *name == "$env" {
+            "$env".into()
+        } else {
+            format!("$env.{name}")
+        },
+        indices,
+    });
+    state.show_env = false;
+    state.focus = Focus::Instructions;
+}
+
+/// Jump to the innermost error handler that covers the selected instruction
+fn go_to_handler(state: &mut State) {
+    let block = state.current_block_mut();
+    let Some(index) = block.list_state.selected() else {
+        state.error = Some("nothing is selected".into());
+        return;
+    };
+    match block
+        .error_handlers
+        .get(index)
+        .and_then(|handlers| handlers.last())
+    {
+        Some(handler) => {
+            let target = handler.target;
+            block.list_state.select(Some(target));
+            state.jump_list.push(JumpState::Goto { previous: index });
+        }
+        None => {
+            state.error = Some("not covered by an error handler".into());
+        }
+    }
+}
+
 fn go_back(state: &mut State) {
     match state.jump_list.pop() {
         Some(JumpState::IntoBlock) => {
@@ -192,6 +445,353 @@ fn go_back(state: &mut State) {
     }
 }
 
+fn toggle_focus(state: &mut State) {
+    match state.focus {
+        Focus::Instructions => {
+            let block = state.current_block_mut();
+            let Some(block_span) = block.view_ir.span else {
+                state.error = Some("source code is not available".into());
+                return;
+            };
+            // Start the cursor at the beginning of the selected instruction's span
+            if let Some(offset) = block
+                .list_state
+                .selected()
+                .and_then(|index| block.view_ir.ir_block.spans.get(index))
+                .and_then(|span| span.start.checked_sub(block_span.start))
+                .filter(|offset| block.source.is_char_boundary(*offset))
+            {
+                block.source_cursor = offset;
+            }
+            state.focus = Focus::Source;
+            select_instruction_at_cursor(state.current_block_mut());
+        }
+        Focus::Source if state.show_loops => {
+            state.focus = Focus::Loops;
+        }
+        Focus::Source | Focus::Loops if state.show_histogram => {
+            state.focus = Focus::Histogram;
+        }
+        Focus::Source | Focus::Loops | Focus::Histogram => {
+            state.focus = Focus::Instructions;
+        }
+    }
+}
+
+/// Move the selection in the instruction list by `delta` rows, skipping folded instructions
+fn move_selection(block: &mut BlockState, delta: isize) {
+    let rows = block.visible_instructions();
+    if rows.is_empty() {
+        return;
+    }
+    let row = match block.list_state.selected() {
+        // The selection might be hidden, so find the nearest row before it
+        Some(selected) => rows
+            .iter()
+            .rposition(|&index| index <= selected)
+            .unwrap_or(0)
+            .saturating_add_signed(delta)
+            .min(rows.len() - 1),
+        None => 0,
+    };
+    block.list_state.select(Some(rows[row]));
+}
+
+/// Unfold the selected instruction if it's folded, or otherwise fold the basic block around it
+fn toggle_block_fold(state: &mut State) {
+    let block = state.current_block_mut();
+    let Some(index) = block.list_state.selected() else {
+        state.error = Some("nothing is selected".into());
+        return;
+    };
+
+    if let Some(position) = block
+        .folds
+        .iter()
+        .rposition(|fold| fold.folded && fold.shown == index && fold.range.len() > 1)
+    {
+        if block.folds[position].auto {
+            block.folds[position].folded = false;
+        } else {
+            block.folds.remove(position);
+        }
+        return;
+    }
+
+    let range = Cfg::new(&block.view_ir.ir_block.instructions).basic_block(index);
+    if let Err(err) = add_fold(block, range) {
+        state.error = Some(err);
+    }
+}
+
+/// Fold the body of the innermost loop that contains the selected instruction
+fn fold_loop(state: &mut State) {
+    let block = state.current_block_mut();
+    let selected = block.list_state.selected();
+    let Some(range) = block
+        .loops
+        .iter()
+        .filter(|l| selected.is_some_and(|index| l.body.binary_search(&index).is_ok()))
+        .min_by_key(|l| l.body.len())
+        .map(|l| l.header..l.body.last().map_or(l.header, |last| last + 1))
+    else {
+        state.error = Some("not in a loop".into());
+        return;
+    };
+    if let Err(err) = add_fold(block, range) {
+        state.error = Some(err);
+    }
+}
+
+fn add_fold(block: &mut BlockState, range: Range<usize>) -> Result<(), String> {
+    if range.len() <= 1 {
+        return Err("nothing to fold".into());
+    }
+    block.list_state.select(Some(range.start));
+    block.folds.push(Fold {
+        shown: range.start,
+        range,
+        folded: true,
+        auto: false,
+    });
+    Ok(())
+}
+
+/// Fold the arguments of all calls, or unfold them if they're all folded already
+fn toggle_call_folds(block: &mut BlockState) {
+    let fold = !block
+        .folds
+        .iter()
+        .filter(|fold| fold.auto)
+        .all(|fold| fold.folded);
+    for call_fold in block.folds.iter_mut().filter(|fold| fold.auto) {
+        call_fold.folded = fold;
+    }
+}
+
+fn toggle_loops(state: &mut State) {
+    state.show_loops = !state.show_loops;
+    state.focus = if state.show_loops {
+        Focus::Loops
+    } else {
+        Focus::Instructions
+    };
+}
+
+fn toggle_histogram(state: &mut State) {
+    state.show_histogram = !state.show_histogram;
+    state.focus = if state.show_histogram {
+        if state.histogram_reachable {
+            load_reachable_opcodes(state);
+        }
+        Focus::Histogram
+    } else {
+        Focus::Instructions
+    };
+}
+
+/// Count the opcodes in every block reachable from the current block, if they haven't been yet
+fn load_reachable_opcodes(state: &mut State) {
+    let Some(block) = state.blocks.last_mut() else {
+        return;
+    };
+    if block.reachable_opcodes.is_some() {
+        return;
+    }
+    let result = data::view_ir(
+        &state.engine,
+        Value::int(block.view_ir.block_id.get() as i64, state.head),
+        false,
+        state.head,
+    )
+    .and_then(|view_ir| data::reachable_blocks(&state.engine, view_ir, state.head));
+    match result {
+        Ok(blocks) => {
+            block.reachable_opcodes = Some(analysis::opcode_histogram(
+                blocks
+                    .iter()
+                    .flat_map(|view_ir| &view_ir.formatted_instructions),
+            ));
+        }
+        Err(err) => {
+            state.histogram_reachable = false;
+            state.error = Some(err.to_string());
+        }
+    }
+}
+
+/// Get the opcode histogram that's shown, for the current block or every block reachable from it
+fn histogram(state: &State) -> Vec<(String, usize)> {
+    let block = state.current_block();
+    match &block.reachable_opcodes {
+        Some(opcodes) if state.histogram_reachable => opcodes.clone(),
+        _ => analysis::opcode_histogram(&block.view_ir.formatted_instructions),
+    }
+}
+
+/// Show only the instructions with the opcode under the cursor in the histogram
+fn filter_opcode(state: &mut State) {
+    let Some((name, _)) = histogram(state).into_iter().nth(state.histogram_cursor) else {
+        return;
+    };
+    let block = state.current_block_mut();
+    let indices: Vec<usize> = block
+        .view_ir
+        .formatted_instructions
+        .iter()
+        .enumerate()
+        .filter(|(_, inst)| analysis::opcode(inst) == name)
+        .map(|(index, _)| index)
+        .collect();
+    if indices.is_empty() {
+        state.error = Some(format!("there are no {name} instructions in this block"));
+        return;
+    }
+    block.list_state.select(indices.first().copied());
+    block.filter = Some(Filter {
+        description: name,
+        indices,
+    });
+    state.focus = Focus::Instructions;
+}
+
+/// Show only the instructions that match the query in the filter prompt, or all of them if it's
+/// empty
+fn apply_filter(state: &mut State) {
+    let query = state.filter_contents.clone();
+    let block = state.current_block_mut();
+    if query.trim().is_empty() {
+        block.filter = None;
+        return;
+    }
+    match filter::filter(block, &query) {
+        Ok(filter) => {
+            // Stay on the selected instruction if it matches
+            if !block
+                .list_state
+                .selected()
+                .is_some_and(|index| filter.indices.contains(&index))
+            {
+                block.list_state.select(filter.indices.first().copied());
+            }
+            block.filter = Some(filter);
+            state.focus = Focus::Instructions;
+        }
+        Err(err) => {
+            state.error = Some(err);
+        }
+    }
+}
+
+/// Move the cursor in the loop outline up or down by `delta` visible loops, and select the header
+/// of the loop in the instruction list
+fn move_loop_cursor(block: &mut BlockState, delta: isize) {
+    let visible = block.visible_loops();
+    let Some(position) = visible
+        .iter()
+        .position(|&index| index == block.loop_cursor)
+        .or_else(|| (!visible.is_empty()).then_some(0))
+    else {
+        return;
+    };
+    let new_position = position.saturating_add_signed(delta).min(visible.len() - 1);
+    block.loop_cursor = visible[new_position];
+    block
+        .list_state
+        .select(Some(block.loops[block.loop_cursor].header));
+}
+
+/// Collapse or expand the loop under the cursor in the loop outline. Collapsing a loop that has
+/// nothing to collapse moves to its parent instead.
+fn collapse_loop(block: &mut BlockState, collapse: bool) {
+    let index = block.loop_cursor;
+    let Some(the_loop) = block.loops.get(index) else {
+        return;
+    };
+    let has_children = block.loops.iter().any(|l| l.parent == Some(index));
+    if collapse && (!has_children || block.collapsed_loops[index]) {
+        if let Some(parent) = the_loop.parent {
+            block.loop_cursor = parent;
+            block.list_state.select(Some(block.loops[parent].header));
+        }
+    } else if has_children {
+        block.collapsed_loops[index] = collapse;
+    }
+}
+
+fn move_source_cursor(block: &mut BlockState, movement: CursorMovement) {
+    let source = block.source.as_str();
+    let cursor = block.source_cursor.min(source.len());
+
+    let line_start = |offset: usize| source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = |offset: usize| {
+        source[offset..]
+            .find('\n')
+            .map(|i| offset + i)
+            .unwrap_or(source.len())
+    };
+    // Find the offset of the given column within a line, stopping at the end of the line
+    let column_offset = |start: usize, column: usize| {
+        source[start..line_end(start)]
+            .char_indices()
+            .nth(column)
+            .map(|(i, _)| start + i)
+            .unwrap_or(line_end(start))
+    };
+    let column = source[line_start(cursor)..cursor].chars().count();
+
+    block.source_cursor = match movement {
+        CursorMovement::Left => source[..cursor]
+            .char_indices()
+            .next_back()
+            .map(|(i, _)| i)
+            .unwrap_or(0),
+        CursorMovement::Right => source[cursor..]
+            .chars()
+            .next()
+            .map(|ch| cursor + ch.len_utf8())
+            .unwrap_or(cursor),
+        CursorMovement::Up => match line_start(cursor) {
+            0 => cursor,
+            start => column_offset(line_start(start - 1), column),
+        },
+        CursorMovement::Down => match line_end(cursor) {
+            end if end == source.len() => cursor,
+            end => column_offset(end + 1, column),
+        },
+    };
+
+    select_instruction_at_cursor(block);
+}
+
+/// Find the indices of all instructions with a span covering the source cursor
+fn instructions_at_cursor(block: &BlockState) -> Vec<usize> {
+    let Some(block_span) = block.view_ir.span else {
+        return vec![];
+    };
+    let position = block_span.start + block.source_cursor;
+    block
+        .view_ir
+        .ir_block
+        .spans
+        .iter()
+        .enumerate()
+        .filter(|(_, span)| span.start <= position && position < span.end)
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Keep the instruction list scrolled to the instructions under the source cursor
+fn select_instruction_at_cursor(block: &mut BlockState) {
+    let covering = instructions_at_cursor(block);
+    let selected = block.list_state.selected();
+    if !selected.is_some_and(|index| covering.contains(&index)) {
+        if let Some(&first) = covering.first() {
+            block.list_state.select(Some(first));
+        }
+    }
+}
+
 fn handle_events(state: &mut State) -> io::Result<()> {
     if event::poll(Duration::from_secs(1))? {
         if let Event::Key(key) = event::read()? {
@@ -217,7 +817,7 @@ fn handle_keypress(state: &mut State, key_event: KeyEvent) {
             state.goto = false;
             match state.goto_contents.parse::<usize>() {
                 Ok(index) => {
-                    if index < state.inst_list.len() {
+                    if index < state.inst_lines.len() {
                         state.goto_contents.clear();
                         // Save so you can jump back with [
                         if let Some(previous) = state.list_state().selected() {
@@ -233,24 +833,165 @@ fn handle_keypress(state: &mut State, key_event: KeyEvent) {
                 }
             }
         }
+        KeyCode::Char(c) if state.filter_prompt => {
+            state.filter_contents.push(c);
+        }
+        KeyCode::Backspace if state.filter_prompt => {
+            state.filter_contents.pop();
+        }
+        KeyCode::Enter if state.filter_prompt => {
+            state.filter_prompt = false;
+            apply_filter(state);
+        }
         KeyCode::Char('q') => {
             state.should_quit = true;
         }
         KeyCode::Char('g') => {
             state.goto = true;
         }
+        KeyCode::Char('/') => {
+            state.filter_prompt = true;
+        }
+        KeyCode::Char('e') => {
+            state.open_editor = true;
+        }
+        KeyCode::Char('o') => {
+            toggle_loops(state);
+        }
+        KeyCode::Char('H') => {
+            toggle_histogram(state);
+        }
+        KeyCode::Char('p') => {
+            state.show_pseudocode = !state.show_pseudocode;
+        }
+        KeyCode::Char('s') => {
+            state.show_ssa = !state.show_ssa;
+            refresh_instruction_list(state);
+        }
+        KeyCode::Char('x') => {
+            toggle_emulator(state);
+        }
+        KeyCode::Char('n') => {
+            step_emulator(state, 1);
+        }
+        KeyCode::Char('N') => {
+            step_emulator(state, MAX_EMULATOR_STEPS);
+        }
+        KeyCode::Char('t') => {
+            state.show_types = !state.show_types;
+            refresh_instruction_list(state);
+        }
+        KeyCode::Char('a') => {
+            state.show_ast = !state.show_ast;
+            if state.show_ast {
+                load_ast(state);
+            }
+        }
+        KeyCode::Up | KeyCode::Char('k') if state.show_env => {
+            state.env_cursor = state.env_cursor.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if state.show_env => {
+            state.env_cursor = state.env_cursor.saturating_add(1);
+        }
+        KeyCode::Enter if state.show_env => {
+            filter_env_var(state);
+        }
+        KeyCode::Up | KeyCode::Char('k') if state.show_provenance => {
+            state.provenance_scroll = state.provenance_scroll.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if state.show_provenance => {
+            state.provenance_scroll = state.provenance_scroll.saturating_add(1);
+        }
         KeyCode::Char(' ') => {
             state.show_inspector = true;
         }
+        KeyCode::Char('v') => {
+            state.show_provenance = true;
+            state.provenance_scroll = 0;
+        }
+        KeyCode::Char('E') => {
+            state.show_env = true;
+        }
+        KeyCode::Char('?') => {
+            state.show_help = !state.show_help;
+        }
         KeyCode::Esc => {
-            state.show_inspector = false;
-            state.goto = false;
+            if state.show_inspector
+                || state.show_provenance
+                || state.show_env
+                || state.show_help
+                || state.goto
+                || state.filter_prompt
+            {
+                state.show_inspector = false;
+                state.show_provenance = false;
+                state.show_env = false;
+                state.show_help = false;
+                state.goto = false;
+                state.filter_prompt = false;
+            } else {
+                state.current_block_mut().filter = None;
+            }
+        }
+        KeyCode::Tab => {
+            toggle_focus(state);
+        }
+        KeyCode::Left | KeyCode::Char('h') if state.focus == Focus::Source => {
+            move_source_cursor(state.current_block_mut(), CursorMovement::Left);
+        }
+        KeyCode::Right | KeyCode::Char('l') if state.focus == Focus::Source => {
+            move_source_cursor(state.current_block_mut(), CursorMovement::Right);
+        }
+        KeyCode::Up | KeyCode::Char('k') if state.focus == Focus::Source => {
+            move_source_cursor(state.current_block_mut(), CursorMovement::Up);
+        }
+        KeyCode::Down | KeyCode::Char('j') if state.focus == Focus::Source => {
+            move_source_cursor(state.current_block_mut(), CursorMovement::Down);
+        }
+        KeyCode::Up | KeyCode::Char('k') if state.focus == Focus::Histogram => {
+            state.histogram_cursor = state.histogram_cursor.saturating_sub(1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if state.focus == Focus::Histogram => {
+            state.histogram_cursor = state.histogram_cursor.saturating_add(1);
+        }
+        KeyCode::Enter if state.focus == Focus::Histogram => {
+            filter_opcode(state);
+        }
+        KeyCode::Char('r') if state.focus == Focus::Histogram => {
+            state.histogram_reachable = !state.histogram_reachable;
+            if state.histogram_reachable {
+                load_reachable_opcodes(state);
+            }
+        }
+        KeyCode::Up | KeyCode::Char('k') if state.focus == Focus::Loops => {
+            move_loop_cursor(state.current_block_mut(), -1);
+        }
+        KeyCode::Down | KeyCode::Char('j') if state.focus == Focus::Loops => {
+            move_loop_cursor(state.current_block_mut(), 1);
+        }
+        KeyCode::Left | KeyCode::Char('h') if state.focus == Focus::Loops => {
+            collapse_loop(state.current_block_mut(), true);
+        }
+        KeyCode::Right | KeyCode::Char('l') if state.focus == Focus::Loops => {
+            collapse_loop(state.current_block_mut(), false);
         }
         KeyCode::Up | KeyCode::Char('k') => {
-            state.list_state_mut().select_previous();
+            move_selection(state.current_block_mut(), -1);
         }
         KeyCode::Down | KeyCode::Char('j') => {
-            state.list_state_mut().select_next();
+            move_selection(state.current_block_mut(), 1);
+        }
+        KeyCode::Char('z') => {
+            toggle_block_fold(state);
+        }
+        KeyCode::Char('Z') => {
+            fold_loop(state);
+        }
+        KeyCode::Char('f') => {
+            toggle_call_folds(state.current_block_mut());
+        }
+        KeyCode::Char('c') => {
+            go_to_handler(state);
         }
         KeyCode::Char('[') => {
             go_back(state);
@@ -274,66 +1015,435 @@ fn instruction_style(instruction: &Instruction) -> Style {
     }
 }
 
-fn make_instruction_list(view_ir_output: &ViewIrOutput) -> List<'static> {
-    view_ir_output
-        .formatted_instructions
-        .iter()
-        .enumerate()
-        .map(|(index, inst)| {
-            let instruction = &view_ir_output.ir_block.instructions[index];
-            let comment = &view_ir_output.ir_block.comments[index];
-            // Parse the formatted instruction into its two components so we can color it
-            let (inst_name, inst_args) = if let Some(split_offset) = inst.find(' ') {
-                let (inst_name, inst_args) = inst.split_at(split_offset);
-                (
-                    inst_name,
-                    inst_args
-                        .split_at(inst_args.find(|ch| ch != ' ').unwrap_or(0))
-                        .1,
-                )
-            } else {
-                (inst.as_str(), "")
-            };
-            Line::from_iter([
-                Span::styled(format!("{index:4}: "), Style::new().dim()),
-                Span::raw(format!("{inst_name:22} ")),
-                // Make it stand out if it's jumpable
-                Span::styled(format!("{inst_args:17}"), instruction_style(instruction)),
-                if !comment.is_empty() {
-                    Span::styled(format!(" # {comment}"), Style::new().dim().italic())
-                } else {
-                    Span::raw("")
-                },
-            ])
-        })
-        .collect()
+/// The style of the shaded column and labels of an error handler region, by its number
+fn region_style(number: usize) -> Style {
+    const COLORS: [Color; 4] = [
+        Color::Indexed(24),
+        Color::Indexed(53),
+        Color::Indexed(22),
+        Color::Indexed(94),
+    ];
+    Style::new().white().bg(COLORS[number % COLORS.len()])
 }
 
-fn ui(frame: &mut Frame, state: &mut State) {
-    let main_layout = Layout::new(
-        Direction::Vertical,
-        [Constraint::Fill(1), Constraint::Max(1)],
-    )
-    .split(frame.size());
+/// The most instructions that are run at once when running to the end of the block, in case it
+/// loops forever
+const MAX_EMULATOR_STEPS: usize = 10_000;
 
-    // Bottom status
-    statusbar_ui(frame, state, main_layout[1]);
+/// Start stepping through the block from the beginning, or stop
+fn toggle_emulator(state: &mut State) {
+    let block = state.current_block_mut();
+    if block.emulator.take().is_none() {
+        block.emulator = Some(Emulator::new(block.view_ir.ir_block.register_count));
+        block.list_state.select(Some(0));
+    }
+}
 
-    let layout = Layout::new(
-        Direction::Horizontal,
-        [Constraint::Fill(1), Constraint::Fill(1)],
-    )
-    .split(main_layout[0]);
+/// Run some instructions in the emulator, and select the next one
+fn step_emulator(state: &mut State, steps: usize) {
+    let engine = &state.engine;
+    let Some(block) = state.blocks.last_mut() else {
+        return;
+    };
+    let Some(emulator) = &mut block.emulator else {
+        state.error = Some("not emulating, press x to start".into());
+        return;
+    };
+    emulator.run(engine, &block.view_ir.ir_block, steps);
+    if emulator.pc < block.view_ir.ir_block.instructions.len() {
+        block.list_state.select(Some(emulator.pc));
+    }
+}
 
-    instructions_ui(frame, state, layout[0]);
-    source_code_ui(frame, state, layout[1]);
+/// Find the name of a variable from an instruction that refers to it
+fn variable_name(block: &BlockState, var_id: nu_protocol::VarId) -> String {
+    Pseudocode::new(&block.view_ir)
+        .variable_name(var_id)
+        .unwrap_or_else(|| format!("var {}", var_id.get()))
+}
 
-    if state.show_inspector {
-        inspector_ui(frame, state);
+/// Rebuild the instruction list after changing how it's shown
+fn refresh_instruction_list(state: &mut State) {
+    if let Some(block) = state.blocks.last() {
+        state.inst_lines = make_instruction_list(block, state.show_ssa, state.show_types);
     }
 }
 
-fn statusbar_ui(frame: &mut Frame, state: &mut State, area: Rect) {
+/// Style inferred types, making it stand out where a value could be anything
+fn types_style(types: &TypeSet) -> Style {
+    if types.is_any() {
+        Style::new().light_yellow().dim()
+    } else {
+        Style::new().light_green().dim()
+    }
+}
+
+/// Replace each register in a formatted instruction, skipping anything inside string literals
+fn rename_registers(formatted: &str, mut rename: impl FnMut(RegId) -> Option<String>) -> String {
+    let mut out = String::with_capacity(formatted.len());
+    let mut chars = formatted.char_indices().peekable();
+    let mut in_string = false;
+    while let Some((offset, ch)) = chars.next() {
+        match ch {
+            '"' => in_string = !in_string,
+            '\\' if in_string => {
+                out.push(ch);
+                if let Some((_, escaped)) = chars.next() {
+                    out.push(escaped);
+                }
+                continue;
+            }
+            '%' if !in_string => {
+                let digits_end = formatted[offset + 1..]
+                    .find(|ch: char| !ch.is_ascii_digit())
+                    .map(|len| offset + 1 + len)
+                    .unwrap_or(formatted.len());
+                if let Some(name) = formatted[offset + 1..digits_end]
+                    .parse()
+                    .ok()
+                    .and_then(|reg| rename(RegId::new(reg)))
+                {
+                    out.push_str(&name);
+                    while chars.next_if(|(offset, _)| *offset < digits_end).is_some() {}
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// Format an instruction with its registers renamed to SSA values. A register that's both used
+/// and written is shown as `%r.used→%r.written`.
+fn ssa_instruction(ssa: &Ssa, instruction: &Instruction, index: usize, formatted: &str) -> String {
+    let uses = registers::uses(instruction);
+    let defs = registers::defs(instruction);
+    rename_registers(formatted, |reg_id| {
+        let used = uses
+            .contains(&reg_id)
+            .then(|| ssa.value(index, reg_id))
+            .flatten()
+            .map(|value| ssa.name(reg_id, value));
+        let written = defs
+            .contains(&reg_id)
+            .then(|| ssa.name(reg_id, SsaValue::Def(index)));
+        match (used, written) {
+            (Some(used), Some(written)) => Some(format!("{used}→{written}")),
+            (used, written) => used.or(written),
+        }
+    })
+}
+
+/// The longest time spent in any instruction of a profiled block, for scaling the heatmap
+fn max_duration(block: &BlockState) -> f64 {
+    block
+        .profile
+        .iter()
+        .map(|profile| profile.duration_ms)
+        .fold(0.0, f64::max)
+}
+
+/// Pick a color from dark to bright red for a fraction of the longest time
+fn heat_color(fraction: f64) -> Color {
+    const RAMP: [u8; 6] = [236, 52, 88, 124, 160, 196];
+    if !fraction.is_finite() {
+        return Color::Indexed(RAMP[0]);
+    }
+    let step = (fraction.clamp(0.0, 1.0) * (RAMP.len() - 1) as f64).round() as usize;
+    Color::Indexed(RAMP[step])
+}
+
+/// Describe the ways a branch never went while profiling, if it ran at all
+fn branch_coverage_note(block: &BlockState, index: usize) -> Option<String> {
+    let branch = block.branches.iter().find(|branch| branch.index == index)?;
+    let target = block.view_ir.ir_block.instructions[index].branch_target()?;
+    match (branch.taken, branch.not_taken) {
+        (false, true) => Some(format!("never jumped to {target}")),
+        (true, false) => Some(format!("never continued to {}", index + 1)),
+        _ => None,
+    }
+}
+
+/// Format a duration in milliseconds with a unit that keeps it short
+fn format_duration(ms: f64) -> String {
+    if ms >= 1000.0 {
+        format!("{:.2}s", ms / 1000.0)
+    } else if ms >= 1.0 {
+        format!("{ms:.2}ms")
+    } else {
+        format!("{:.0}µs", ms * 1000.0)
+    }
+}
+
+fn make_instruction_list(
+    block: &BlockState,
+    show_ssa: bool,
+    show_types: bool,
+) -> Vec<Text<'static>> {
+    let view_ir_output = &block.view_ir;
+    let pseudocode = Pseudocode::new(view_ir_output);
+    let ssa = show_ssa.then(|| {
+        Ssa::new(
+            &view_ir_output.ir_block.instructions,
+            view_ir_output.ir_block.register_count,
+        )
+    });
+
+    // Number the error handler regions in the order they're pushed, and find the range of
+    // instructions that each one covers
+    let mut regions: Vec<(ErrorHandler, Range<usize>)> = vec![];
+    for (index, handlers) in block.error_handlers.iter().enumerate() {
+        for handler in handlers {
+            match regions.iter_mut().find(|(region, _)| region == handler) {
+                Some((_, range)) => range.end = index + 1,
+                None => regions.push((*handler, index..index + 1)),
+            }
+        }
+    }
+    regions.sort_by_key(|(handler, _)| handler.pushed_at);
+    let region_number =
+        |handler: &ErrorHandler| regions.iter().position(|(region, _)| region == handler);
+    let depth = block.error_handlers.iter().map(Vec::len).max().unwrap_or(0);
+    let max_duration = max_duration(block);
+
+    view_ir_output
+        .formatted_instructions
+        .iter()
+        .enumerate()
+        .map(|(index, inst)| {
+            let instruction = &view_ir_output.ir_block.instructions[index];
+            let comment = &view_ir_output.ir_block.comments[index];
+            let inst = &match &ssa {
+                Some(ssa) => ssa_instruction(ssa, instruction, index, inst),
+                None => inst.clone(),
+            };
+            // Parse the formatted instruction into its two components so we can color it
+            let (inst_name, inst_args) = if let Some(split_offset) = inst.find(' ') {
+                let (inst_name, inst_args) = inst.split_at(split_offset);
+                (
+                    inst_name,
+                    inst_args
+                        .split_at(inst_args.find(|ch| ch != ' ').unwrap_or(0))
+                        .1,
+                )
+            } else {
+                (inst.as_str(), "")
+            };
+            // Mark instructions that have lint warnings in the gutter
+            let gutter = if block.lints.iter().any(|lint| lint.index == Some(index)) {
+                Span::styled("▲", Style::new().light_yellow())
+            } else {
+                Span::raw(" ")
+            };
+            // Shade a column for each error handler that covers the instruction, with the
+            // region's number at the start
+            let regions_column = (0..depth).map(|level| {
+                match block.error_handlers[index]
+                    .get(level)
+                    .and_then(|handler| Some((handler, region_number(handler)?)))
+                {
+                    Some((handler, number)) => {
+                        let label = if regions[number].1.start == index {
+                            format!("{}", (number + 1) % 10)
+                        } else if block.error_handlers[index].last() == Some(handler) {
+                            "│".into()
+                        } else {
+                            " ".into()
+                        };
+                        Span::styled(label, region_style(number))
+                    }
+                    None => Span::raw(" "),
+                }
+            });
+            // Label the instructions that push a handler, and the handlers themselves
+            let region_labels = regions
+                .iter()
+                .enumerate()
+                .filter_map(|(number, (handler, _))| {
+                    if handler.pushed_at == index {
+                        Some(format!(" try {} ", number + 1))
+                    } else if handler.target == index {
+                        Some(format!(" catch {} ", number + 1))
+                    } else {
+                        None
+                    }
+                    .map(|label| Span::styled(label, region_style(number)))
+                })
+                .collect::<Vec<_>>();
+            let line = Line::from_iter(
+                [gutter]
+                    .into_iter()
+                    .chain(regions_column)
+                    .chain([Span::styled(format!("{index:4}: "), Style::new().dim())])
+                    // Show how often the instruction ran and how long it took, if profiled
+                    .chain(block.profile.get(index).map(|profile| {
+                        if profile.hits == 0 {
+                            Span::styled(
+                                format!("{:>6}  {:>8} ", "✗", ""),
+                                Style::new().light_red(),
+                            )
+                        } else {
+                            Span::styled(
+                                format!(
+                                    "{:>6}× {:>8} ",
+                                    profile.hits,
+                                    format_duration(profile.duration_ms)
+                                ),
+                                Style::new().bg(heat_color(profile.duration_ms / max_duration)),
+                            )
+                        }
+                    }))
+                    .chain([
+                        Span::raw(format!("{inst_name:22} ")),
+                        // Make it stand out if it's jumpable
+                        Span::styled(format!("{inst_args:17}"), instruction_style(instruction)),
+                    ])
+                    // Show the inferred type of the register that's written
+                    .chain(
+                        block.types.output[index]
+                            .as_ref()
+                            .filter(|_| show_types)
+                            .map(|(reg_id, types)| {
+                                Span::styled(format!(" ⇒ {reg_id}: {types}"), types_style(types))
+                            }),
+                    )
+                    // Show results that could be computed at compile time
+                    .chain(
+                        block.constants[index]
+                            .as_ref()
+                            .filter(|_| analysis::is_foldable(instruction))
+                            .map(|value| {
+                                Span::styled(
+                                    format!(" ≡ {}", analysis::display_constant(value)),
+                                    Style::new().light_blue().italic(),
+                                )
+                            }),
+                    )
+                    // Point out the ways a branch that ran never went
+                    .chain(branch_coverage_note(block, index).map(|note| {
+                        Span::styled(format!(" ◐ {note}"), Style::new().light_red().italic())
+                    }))
+                    .chain(region_labels)
+                    // Show what was passed to calls
+                    .chain(pseudocode.call_line(index).map(|call_line| {
+                        Span::styled(format!(" » {call_line}"), Style::new().light_cyan().dim())
+                    }))
+                    .chain([if !comment.is_empty() {
+                        Span::styled(format!(" # {comment}"), Style::new().dim().italic())
+                    } else {
+                        Span::raw("")
+                    }]),
+            );
+            // Show the phi nodes before the instruction they're at, so that each instruction is
+            // still one item in the list
+            let mut text = Text::default();
+            for (ssa, phi) in ssa.iter().flat_map(|ssa| {
+                ssa.phis
+                    .iter()
+                    .filter(|phi| phi.index == index)
+                    .map(move |phi| (ssa, phi))
+            }) {
+                let incoming = phi
+                    .incoming
+                    .iter()
+                    .map(|(pred, value)| {
+                        let name = ssa.name(phi.reg_id, *value);
+                        match pred {
+                            Some(pred) => format!("{name} from {pred}"),
+                            None => format!("{name} from entry"),
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                text.push_line(Line::styled(
+                    format!(
+                        "{:width$}{} = φ({incoming})",
+                        "",
+                        ssa.name(phi.reg_id, SsaValue::Phi(index)),
+                        width = 7 + depth,
+                    ),
+                    Style::new().light_magenta().dim(),
+                ));
+            }
+            text.push_line(line);
+            // Dim code that can never run
+            if block.unreachable[index].is_some() {
+                text.dim()
+            } else {
+                text
+            }
+        })
+        .collect()
+}
+
+fn ui(frame: &mut Frame, state: &mut State) {
+    let main_layout = Layout::new(
+        Direction::Vertical,
+        [Constraint::Fill(1), Constraint::Max(1)],
+    )
+    .split(frame.size());
+
+    // Bottom status
+    statusbar_ui(frame, state, main_layout[1]);
+
+    let show_emulator = state.current_block().emulator.is_some();
+    let pane_count = 2
+        + state.show_ast as usize
+        + state.show_pseudocode as usize
+        + state.show_loops as usize
+        + state.show_histogram as usize
+        + show_emulator as usize;
+    let layout = Layout::new(Direction::Horizontal, vec![Constraint::Fill(1); pane_count])
+        .split(main_layout[0]);
+
+    instructions_ui(frame, state, layout[0]);
+    source_code_ui(frame, state, layout[1]);
+
+    let mut next_pane = 2;
+    if state.show_ast {
+        ast_ui(frame, state, layout[next_pane]);
+        next_pane += 1;
+    }
+
+    if state.show_pseudocode {
+        pseudocode_ui(frame, state, layout[next_pane]);
+        next_pane += 1;
+    }
+
+    if state.show_loops {
+        loops_ui(frame, state, layout[next_pane]);
+        next_pane += 1;
+    }
+
+    if state.show_histogram {
+        histogram_ui(frame, state, layout[next_pane]);
+        next_pane += 1;
+    }
+
+    if show_emulator {
+        emulator_ui(frame, state, layout[next_pane]);
+    }
+
+    if state.show_inspector {
+        inspector_ui(frame, state);
+    }
+
+    if state.show_provenance {
+        provenance_ui(frame, state);
+    }
+
+    if state.show_env {
+        env_ui(frame, state);
+    }
+
+    if state.show_help {
+        help_ui(frame);
+    }
+}
+
+fn statusbar_ui(frame: &mut Frame, state: &mut State, area: Rect) {
     let key_style = Style::new().light_blue().bold();
     let desc_style = Style::new().italic();
 
@@ -357,6 +1467,84 @@ fn statusbar_ui(frame: &mut Frame, state: &mut State, area: Rect) {
             area.y,
         );
         frame.render_widget(widget, area);
+    } else if state.filter_prompt {
+        let widget = Line::from_iter([
+            Span::styled("Filter: ", desc_style),
+            Span::raw(state.filter_contents.as_str()),
+        ]);
+        frame.set_cursor(
+            u16::try_from(widget.width())
+                .map(|width| (area.x + width).min(area.right()))
+                .unwrap_or(area.right()),
+            area.y,
+        );
+        frame.render_widget(widget, area);
+    } else if state.focus == Focus::Loops {
+        frame.render_widget(
+            Line::from_iter([
+                Span::styled("<q>", key_style),
+                Span::styled(" quit  ", desc_style),
+                Span::styled("<tab>", key_style),
+                Span::styled(" back to instructions  ", desc_style),
+                Span::styled("<o>", key_style),
+                Span::styled(" close loops  ", desc_style),
+                Span::styled("<↑/↓/k/j>", key_style),
+                Span::styled(" navigate  ", desc_style),
+                Span::styled("<←/→/h/l>", key_style),
+                Span::styled(" collapse/expand  ", desc_style),
+            ]),
+            area,
+        );
+    } else if state.focus == Focus::Histogram {
+        frame.render_widget(
+            Line::from_iter([
+                Span::styled("<q>", key_style),
+                Span::styled(" quit  ", desc_style),
+                Span::styled("<tab>", key_style),
+                Span::styled(" back to instructions  ", desc_style),
+                Span::styled("<H>", key_style),
+                Span::styled(" close opcodes  ", desc_style),
+                Span::styled("<↑/↓/k/j>", key_style),
+                Span::styled(" navigate  ", desc_style),
+                Span::styled("<enter>", key_style),
+                Span::styled(" show only this opcode  ", desc_style),
+                Span::styled("<r>", key_style),
+                Span::styled(" this block/reachable blocks  ", desc_style),
+            ]),
+            area,
+        );
+    } else if state.focus == Focus::Source {
+        frame.render_widget(
+            Line::from_iter([
+                Span::styled("<q>", key_style),
+                Span::styled(" quit  ", desc_style),
+                Span::styled("<tab>", key_style),
+                Span::styled(" back to instructions  ", desc_style),
+                Span::styled("<e>", key_style),
+                Span::styled(" edit  ", desc_style),
+                Span::styled("<←/↓/↑/→/h/j/k/l>", key_style),
+                Span::styled(" move cursor  ", desc_style),
+            ]),
+            area,
+        );
+    } else if state.current_block().emulator.is_some() {
+        frame.render_widget(
+            Line::from_iter([
+                Span::styled("<q>", key_style),
+                Span::styled(" quit  ", desc_style),
+                Span::styled("<n>", key_style),
+                Span::styled(" step  ", desc_style),
+                Span::styled("<N>", key_style),
+                Span::styled(" run  ", desc_style),
+                Span::styled("<x>", key_style),
+                Span::styled(" stop emulating  ", desc_style),
+                Span::styled("<space>", key_style),
+                Span::styled(" inspect  ", desc_style),
+                Span::styled("<↑/↓/k/j>", key_style),
+                Span::styled(" navigate  ", desc_style),
+            ]),
+            area,
+        );
     } else {
         frame.render_widget(
             Line::from_iter([
@@ -366,47 +1554,228 @@ fn statusbar_ui(frame: &mut Frame, state: &mut State, area: Rect) {
                 Span::styled(" inspect  ", desc_style),
                 Span::styled("<g>", key_style),
                 Span::styled(" goto  ", desc_style),
+                Span::styled("</>", key_style),
+                Span::styled(" filter  ", desc_style),
                 Span::styled("<↑/↓/k/j>", key_style),
                 Span::styled(" navigate  ", desc_style),
                 Span::styled("<[/]>", key_style),
                 Span::styled(" jump back/fwd  ", desc_style),
+                Span::styled("<c>", key_style),
+                Span::styled(" catch  ", desc_style),
+                Span::styled("<tab>", key_style),
+                Span::styled(" source  ", desc_style),
+                Span::styled("<e>", key_style),
+                Span::styled(" edit  ", desc_style),
+                Span::styled("<a>", key_style),
+                Span::styled(" ast  ", desc_style),
+                Span::styled("<o>", key_style),
+                Span::styled(" loops  ", desc_style),
+                Span::styled("<p>", key_style),
+                Span::styled(" pseudocode  ", desc_style),
+                Span::styled("<z/Z/f>", key_style),
+                Span::styled(" fold block/loop/args  ", desc_style),
+                Span::styled("<?>", key_style),
+                Span::styled(" all keys  ", desc_style),
             ]),
             area,
         );
     }
 }
 
+fn pane_block<'a>(title: impl Into<Line<'a>>, focused: bool) -> Block<'a> {
+    let block = Block::bordered().title(title);
+    if focused {
+        block.border_style(Style::new().light_blue())
+    } else {
+        block
+    }
+}
+
 fn instructions_ui(frame: &mut Frame, state: &mut State, area: Rect) {
     let inner_area = area.inner(Margin {
         horizontal: 0,
         vertical: 1,
     });
 
+    // Mark the instructions that were compiled from the code under the source cursor
+    let covering = if state.focus == Focus::Source {
+        instructions_at_cursor(state.current_block())
+    } else {
+        vec![]
+    };
+
+    let focused = state.focus == Focus::Instructions;
+    let show_ssa = state.show_ssa;
+    let inst_lines = &state.inst_lines;
+
+    // Folded instructions are hidden, so the list has a row for each visible instruction
+    let block = state.blocks.last_mut().expect("State.blocks is empty!");
+    if let Some(selected) = block.list_state.selected() {
+        block.reveal(selected);
+    }
+    let rows = block.visible_instructions();
+
+    let items = rows.iter().map(|&index| {
+        let mut text = inst_lines[index].clone();
+        if let Some(fold) = block
+            .folds
+            .iter()
+            .find(|fold| fold.folded && fold.shown == index && fold.range.len() > 1)
+        {
+            let hidden = fold.range.len() - 1;
+            text.push_span(Span::styled(
+                if fold.auto {
+                    format!(" ⋯ {hidden} for arguments")
+                } else {
+                    format!(" ⋯ {hidden} folded")
+                },
+                Style::new().dim().italic(),
+            ));
+        }
+        let item = ListItem::new(text);
+        if covering.contains(&index) {
+            item.style(Style::new().on_dark_gray())
+        } else {
+            item
+        }
+    });
+
+    // The block's list state is kept in terms of instruction indices, except for the offset which
+    // is in rows
+    let selected_row = block
+        .list_state
+        .selected()
+        .and_then(|selected| rows.iter().position(|&index| index == selected));
+    let mut list_state = ListState::default()
+        .with_offset(block.list_state.offset())
+        .with_selected(selected_row);
+
+    let mut title = Line::from(Span::styled(
+        if show_ssa {
+            "IR instructions (SSA)"
+        } else {
+            "IR instructions"
+        },
+        Style::new().bold(),
+    ));
+    if let Some(filter) = &block.filter {
+        title.push_span(Span::styled(
+            format!(
+                " only {} ({} of {})",
+                filter.description,
+                rows.len(),
+                inst_lines.len()
+            ),
+            Style::new().light_yellow(),
+        ));
+    }
+
     frame.render_stateful_widget(
-        state
-            .inst_list
-            .clone()
-            .block(Block::bordered().title(Span::styled("IR instructions", Style::new().bold())))
+        List::new(items)
+            .block(pane_block(title, focused))
             .highlight_style(Style::new().reversed()),
         area,
-        state.list_state_mut(),
+        &mut list_state,
     );
+    *block.list_state.offset_mut() = list_state.offset();
 
     frame.render_stateful_widget(
         Scrollbar::new(ScrollbarOrientation::VerticalRight),
         inner_area,
-        &mut ScrollbarState::new(state.inst_list.len())
-            .position(state.list_state().selected().unwrap_or(0)),
+        &mut ScrollbarState::new(rows.len()).position(selected_row.unwrap_or(0)),
     );
 }
 
+/// Split `source` into lines, styling byte ranges of it. Later highlights are patched over earlier
+/// ones where they overlap. A highlight starting at the end of a line is shown as a trailing space,
+/// so that a cursor is still visible there.
+fn highlight_source<'a>(source: &'a str, highlights: &[(Range<usize>, Style)]) -> Text<'a> {
+    let mut text = Text::default();
+    let mut line_start = 0;
+
+    for line in source.split('\n') {
+        let line_end = line_start + line.strip_suffix('\r').unwrap_or(line).len();
+
+        let mut boundaries = vec![line_start, line_end];
+        for (range, _) in highlights {
+            boundaries.extend(
+                [range.start, range.end]
+                    .into_iter()
+                    .filter(|offset| (line_start..line_end).contains(offset)),
+            );
+        }
+        boundaries.sort_unstable();
+        boundaries.dedup();
+
+        let mut spans: Vec<Span> = boundaries
+            .windows(2)
+            .map(|window| {
+                let segment = window[0]..window[1];
+                let style = highlights
+                    .iter()
+                    .filter(|(range, _)| range.start <= segment.start && segment.end <= range.end)
+                    .fold(Style::new(), |style, (_, patch)| style.patch(*patch));
+                Span::styled(source.get(segment).unwrap_or(""), style)
+            })
+            .collect();
+
+        if let Some(style) = highlights
+            .iter()
+            .filter(|(range, _)| range.start == line_end)
+            .map(|(_, style)| *style)
+            .reduce(|style, patch| style.patch(patch))
+        {
+            spans.push(Span::styled(" ", style));
+        }
+
+        text.push_line(Line::from(spans));
+        line_start += line.len() + 1;
+    }
+
+    text
+}
+
+/// Shade the code that each profiled instruction came from by how long it took. Spans that contain
+/// others are shaded first, so that the time spent in nested code shows through.
+fn source_heatmap(block: &BlockState, block_span: nu_protocol::Span) -> Vec<(Range<usize>, Style)> {
+    let max_duration = max_duration(block);
+    let mut spans: Vec<_> = block
+        .profile
+        .iter()
+        .zip(&block.view_ir.ir_block.spans)
+        .filter(|(profile, span)| {
+            profile.hits > 0 && span.start >= block_span.start && span.end <= block_span.end
+        })
+        .map(|(profile, span)| {
+            (
+                span.start - block_span.start..span.end - block_span.start,
+                profile.duration_ms,
+            )
+        })
+        .collect();
+    spans.sort_by_key(|(range, _)| std::cmp::Reverse(range.len()));
+    spans
+        .into_iter()
+        .map(|(range, duration)| (range, Style::new().bg(heat_color(duration / max_duration))))
+        .collect()
+}
+
+/// Count the number of lines before the line containing `offset`
+fn line_index(source: &str, offset: usize) -> usize {
+    source.as_bytes()[..offset.min(source.len())]
+        .iter()
+        .filter(|&&byte| byte == b'\n')
+        .count()
+}
+
 fn source_code_ui(frame: &mut Frame, state: &mut State, area: Rect) {
+    let focused = state.focus == Focus::Source;
     let source_code_title = Span::styled("Source code", Style::new().bold());
 
     let fail = |frame: &mut Frame| {
         frame.render_widget(
             Paragraph::new(Span::styled("(not available)", Style::new().dim().italic()))
-                .block(Block::bordered().title(source_code_title.clone())),
+                .block(pane_block(source_code_title.clone(), focused)),
             area,
         );
     };
@@ -419,88 +1788,206 @@ fn source_code_ui(frame: &mut Frame, state: &mut State, area: Rect) {
         return fail(frame);
     };
 
-    // Highlight the span of the selected instruction
+    let focus_range = if focused {
+        // Show the cursor over the character it's on
+        let cursor = block.source_cursor.min(block.source.len());
+        let char_len = block.source[cursor..]
+            .chars()
+            .next()
+            .map(char::len_utf8)
+            .unwrap_or(1);
+        Some((
+            cursor..cursor + char_len,
+            Style::new().light_yellow().reversed().bold(),
+        ))
+    } else {
+        // Highlight the span of the selected instruction
+        block
+            .list_state
+            .selected()
+            .and_then(|index| block.view_ir.ir_block.spans.get(index))
+            .filter(|span| span.start >= block_span.start && span.end <= block_span.end)
+            .map(|span| {
+                (
+                    span.start - block_span.start..span.end - block_span.start,
+                    Style::new().light_blue().reversed().bold(),
+                )
+            })
+    };
 
-    let highlighted_span = block
-        .list_state
-        .selected()
-        .and_then(|index| block.view_ir.ir_block.spans.get(index).cloned())
-        .unwrap_or(nu_protocol::Span::unknown());
+    let mut highlights = block.source_highlights.clone();
+    highlights.extend(source_heatmap(block, block_span));
+    highlights.extend(focus_range.clone());
 
-    let mut text = Text::default();
-    let mut focus_start = 0;
-    let mut focus_end = 0;
-
-    if highlighted_span.start >= block_span.start && highlighted_span.end <= block_span.end {
-        let start = highlighted_span.start - block_span.start;
-        let end = highlighted_span.end - block_span.start;
-        let (initial, next) = block.source.split_at(start);
-        let (highlighted, final_part) = next.split_at(end - start);
-
-        // First, push the initial lines that have no style
-        let initial_line_count = initial.lines().count();
-        text.extend(
-            initial
-                .lines()
-                .take(initial_line_count.saturating_sub(1))
-                .map(Line::raw),
-        );
+    let mut text = highlight_source(&block.source, &highlights);
 
-        // The unstyled part of the last initial line
-        if let Some(unstyled_part_of_last_initial_line) = initial.lines().last() {
-            text.push_line(unstyled_part_of_last_initial_line);
-        }
+    let (focus_start, focus_end) = if let Some((range, _)) = &focus_range {
+        (
+            line_index(&block.source, range.start) as i64,
+            line_index(&block.source, range.end.saturating_sub(1).max(range.start)) as i64 + 1,
+        )
+    } else {
+        (0, 0)
+    };
 
-        // Focus the beginning of the highlight
-        focus_start = text.lines.len().saturating_sub(1) as i64;
+    let mut title = Line::from(source_code_title);
 
-        // Now, the highlighted part
-        let style = Style::new().light_blue().reversed().bold();
-        let styled_line_count = highlighted.lines().count();
-        let mut lines = highlighted.lines();
-        if let Some(highlighted_part_of_last_initial_line) = lines.next() {
-            text.push_span(Span::styled(highlighted_part_of_last_initial_line, style));
-        }
-        text.extend(
-            (&mut lines)
-                .take(styled_line_count.saturating_sub(2))
-                .map(|line| Line::styled(line, style)),
-        );
-        if let Some(highlighted_part_of_first_final_line) = lines.next() {
-            text.push_line(Span::styled(highlighted_part_of_first_final_line, style));
+    // Number the lines with their real line numbers in the file, and line the first line up with
+    // the column it starts at
+    if let Some(file) = &block.source_file {
+        let width = (file.first_line + text.lines.len()).to_string().len();
+        for (index, line) in text.lines.iter_mut().enumerate() {
+            if index == 0 {
+                line.spans
+                    .insert(0, Span::raw(" ".repeat(file.first_column - 1)));
+            }
+            line.spans.insert(
+                0,
+                Span::styled(
+                    format!("{:>width$} │ ", file.first_line + index),
+                    Style::new().dim(),
+                ),
+            );
         }
 
-        // The unstyled part of the first final line
-        if let Some(unstyled_part_of_first_final_line) = final_part.lines().next() {
-            text.push_span(unstyled_part_of_first_final_line);
+        title.push_span(Span::raw(format!(" {}", file.name)));
+        if let Some((line, column)) = focus_range
+            .as_ref()
+            .and_then(|(range, _)| block.source_location(range.start))
+        {
+            title.push_span(Span::styled(
+                format!(":{line}:{column}"),
+                Style::new().dim(),
+            ));
         }
-
-        // Focus the end of the highlight
-        focus_end = text.lines.len() as i64;
-
-        // Finally, push the final lines that have no style.
-        text.extend(final_part.lines().skip(1).map(Line::raw));
-    } else {
-        text = Text::raw(&block.source);
     }
 
-    // Calculate a scroll that will keep the focus near the middle
     let total_y = text.lines.len() as i64;
-    let focus_y = focus_end - focus_start;
     let inner_area = area.inner(Margin {
         horizontal: 0,
         vertical: 1,
     });
-    let inner_height = inner_area.height as i64;
+    let (scroll, middle) = centered_scroll(total_y, focus_start, focus_end, inner_area.height);
+
+    frame.render_widget(
+        Paragraph::new(text)
+            .scroll((scroll, 0))
+            .block(pane_block(title, focused)),
+        area,
+    );
+
+    frame.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+        inner_area,
+        &mut ScrollbarState::new(total_y as usize).position(middle.max(0) as usize),
+    );
+}
+
+/// Calculate a scroll that will keep the focus near the middle. Also returns the middle line.
+fn centered_scroll(total_y: i64, focus_start: i64, focus_end: i64, height: u16) -> (u16, i64) {
+    let focus_y = focus_end - focus_start;
+    let inner_height = height as i64;
     let middle = focus_start + focus_y / 2;
     let middle_top = middle - inner_height / 2;
     let limit = (total_y - inner_height / 2).max(0);
     let scroll = u16::try_from(middle_top.clamp(0, limit)).unwrap_or(u16::MAX);
+    (scroll, middle)
+}
+
+/// Add the lines for an AST tree, expanding only the nodes along `path`. Returns the line index of
+/// the node at the end of the path.
+fn push_ast_lines(
+    lines: &mut Vec<Line<'static>>,
+    nodes: &[AstNode],
+    path: &[usize],
+    depth: usize,
+) -> Option<usize> {
+    let mut target = None;
+    for (index, node) in nodes.iter().enumerate() {
+        let on_path = path.first() == Some(&index);
+        let marker = match (node.children.is_empty(), on_path) {
+            (true, _) => "  ",
+            (false, true) => "▾ ",
+            (false, false) => "▸ ",
+        };
+        let style = if on_path && path.len() == 1 {
+            target = Some(lines.len());
+            Style::new().light_blue().reversed().bold()
+        } else if on_path {
+            Style::new().bold()
+        } else {
+            Style::new()
+        };
+        lines.push(Line::from_iter([
+            Span::raw("  ".repeat(depth)),
+            Span::styled(marker, Style::new().dim()),
+            Span::styled(node.label.clone(), style),
+        ]));
+        if on_path {
+            target = push_ast_lines(lines, &node.children, &path[1..], depth + 1).or(target);
+        }
+    }
+    target
+}
+
+fn ast_ui(frame: &mut Frame, state: &mut State, area: Rect) {
+    let title = Span::styled("AST", Style::new().bold());
+    let block = state.current_block();
+
+    let nodes = match &block.ast {
+        Some(Ok(nodes)) => nodes,
+        Some(Err(err)) => {
+            frame.render_widget(
+                Paragraph::new(Span::styled(err.as_str(), Style::new().dim().italic()))
+                    .wrap(Wrap { trim: true })
+                    .block(pane_block(title, false)),
+                area,
+            );
+            return;
+        }
+        None => {
+            frame.render_widget(
+                Paragraph::new(Span::styled("(not available)", Style::new().dim().italic()))
+                    .block(pane_block(title, false)),
+                area,
+            );
+            return;
+        }
+    };
+
+    // Expand the node that the selected instruction was compiled from
+    let path = block
+        .view_ir
+        .span
+        .zip(
+            block
+                .list_state
+                .selected()
+                .and_then(|index| block.view_ir.ir_block.spans.get(index)),
+        )
+        .filter(|(block_span, span)| span.start >= block_span.start && span.end <= block_span.end)
+        .map(|(block_span, span)| {
+            ast::find_path(
+                nodes,
+                &(span.start - block_span.start..span.end - block_span.start),
+            )
+        })
+        .unwrap_or_default();
+
+    let mut lines = vec![];
+    let target = push_ast_lines(&mut lines, nodes, &path, 0).unwrap_or(0) as i64;
+
+    let total_y = lines.len() as i64;
+    let inner_area = area.inner(Margin {
+        horizontal: 0,
+        vertical: 1,
+    });
+    let (scroll, middle) = centered_scroll(total_y, target, target + 1, inner_area.height);
 
     frame.render_widget(
-        Paragraph::new(text)
+        Paragraph::new(lines)
             .scroll((scroll, 0))
-            .block(Block::bordered().title(source_code_title)),
+            .block(pane_block(title, false)),
         area,
     );
 
@@ -511,13 +1998,237 @@ fn source_code_ui(frame: &mut Frame, state: &mut State, area: Rect) {
     );
 }
 
-fn inspector_ui(frame: &mut Frame, state: &mut State) {
-    // Place the dialog in the center
-    let v_layout = Layout::new(
-        Direction::Vertical,
-        [
-            Constraint::Fill(1),
-            Constraint::Max(20),
+fn pseudocode_ui(frame: &mut Frame, state: &mut State, area: Rect) {
+    let block = state.current_block_mut();
+    let selected = block.list_state.selected();
+    let pseudocode = block
+        .pseudocode
+        .get_or_insert_with(|| Pseudocode::new(&block.view_ir).decompile());
+
+    // Highlight the lines that the selected instruction was decompiled into
+    let mut target = None;
+    let lines: Vec<Line> = pseudocode
+        .iter()
+        .enumerate()
+        .map(|(line_index, line)| {
+            let is_selected = selected.is_some_and(|index| line.instructions.contains(&index));
+            if is_selected && target.is_none() {
+                target = Some(line_index);
+            }
+            let gutter = match line.instructions.iter().min() {
+                Some(first) => format!("{first:4} "),
+                None => "     ".into(),
+            };
+            let line = Line::from_iter([
+                Span::styled(gutter, Style::new().dim()),
+                Span::raw("    ".repeat(line.depth)),
+                if line.text.starts_with('#') {
+                    Span::styled(line.text.as_str(), Style::new().dim().italic())
+                } else {
+                    Span::raw(line.text.as_str())
+                },
+            ]);
+            if is_selected {
+                line.style(Style::new().on_dark_gray())
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    let total_y = lines.len() as i64;
+    let inner_area = area.inner(Margin {
+        horizontal: 0,
+        vertical: 1,
+    });
+    let target = target.unwrap_or(0) as i64;
+    let (scroll, middle) = centered_scroll(total_y, target, target + 1, inner_area.height);
+
+    frame.render_widget(
+        Paragraph::new(lines).scroll((scroll, 0)).block(pane_block(
+            Span::styled("Pseudocode", Style::new().bold()),
+            false,
+        )),
+        area,
+    );
+
+    frame.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+        inner_area,
+        &mut ScrollbarState::new(total_y as usize).position(middle.max(0) as usize),
+    );
+}
+
+fn loops_ui(frame: &mut Frame, state: &mut State, area: Rect) {
+    let focused = state.focus == Focus::Loops;
+    let block = state.current_block();
+    let instructions = &block.view_ir.ir_block.instructions;
+
+    // The innermost loop containing the selected instruction
+    let selected = block.list_state.selected();
+    let current = block
+        .loops
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| selected.is_some_and(|index| l.body.binary_search(&index).is_ok()))
+        .min_by_key(|(_, l)| l.body.len())
+        .map(|(index, _)| index);
+
+    let mut lines = vec![];
+    let mut target = 0;
+    for index in block.visible_loops() {
+        let the_loop = &block.loops[index];
+        let depth = the_loop.depth(&block.loops);
+        let has_children = block.loops.iter().any(|l| l.parent == Some(index));
+        let marker = match (has_children, block.collapsed_loops[index]) {
+            (false, _) => "  ",
+            (true, false) => "▾ ",
+            (true, true) => "▸ ",
+        };
+        let label = match instructions[the_loop.header] {
+            Instruction::Iterate { dst, stream, .. } => format!("for {dst} in {stream}"),
+            _ => "loop".into(),
+        };
+        let exit = the_loop
+            .exit
+            .map(|exit| format!(", exits to {exit}"))
+            .unwrap_or_default();
+        let style = if focused && index == block.loop_cursor {
+            target = lines.len();
+            Style::new().light_blue().reversed().bold()
+        } else if current == Some(index) {
+            if !focused {
+                target = lines.len();
+            }
+            Style::new().bold()
+        } else {
+            Style::new()
+        };
+        lines.push(Line::from_iter([
+            Span::raw("  ".repeat(depth)),
+            Span::styled(marker, Style::new().dim()),
+            Span::styled(format!("{label} at {}", the_loop.header), style),
+            Span::styled(
+                format!(" ({} instructions{exit})", the_loop.body.len()),
+                Style::new().dim(),
+            ),
+        ]));
+    }
+
+    let title = Span::styled("Loops", Style::new().bold());
+    if lines.is_empty() {
+        frame.render_widget(
+            Paragraph::new(Span::styled("(no loops)", Style::new().dim().italic()))
+                .block(pane_block(title, focused)),
+            area,
+        );
+        return;
+    }
+
+    let total_y = lines.len() as i64;
+    let inner_area = area.inner(Margin {
+        horizontal: 0,
+        vertical: 1,
+    });
+    let target = target as i64;
+    let (scroll, middle) = centered_scroll(total_y, target, target + 1, inner_area.height);
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .scroll((scroll, 0))
+            .block(pane_block(title, focused)),
+        area,
+    );
+
+    frame.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+        inner_area,
+        &mut ScrollbarState::new(total_y as usize).position(middle.max(0) as usize),
+    );
+}
+
+fn histogram_ui(frame: &mut Frame, state: &mut State, area: Rect) {
+    let focused = state.focus == Focus::Histogram;
+    let histogram = histogram(state);
+    state.histogram_cursor = state
+        .histogram_cursor
+        .min(histogram.len().saturating_sub(1));
+
+    let block = state.current_block();
+    let selected_opcode = block
+        .list_state
+        .selected()
+        .and_then(|index| block.view_ir.formatted_instructions.get(index))
+        .map(|inst| analysis::opcode(inst));
+
+    let name_width = histogram
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    let max_count = histogram.iter().map(|(_, count)| *count).max().unwrap_or(1);
+    let count_width = max_count.to_string().len();
+    // Leave room for the borders and the spaces between the columns
+    let bar_width = (area.width as usize).saturating_sub(name_width + count_width + 4);
+
+    let lines: Vec<Line> = histogram
+        .iter()
+        .enumerate()
+        .map(|(position, (name, count))| {
+            let style = if focused && position == state.histogram_cursor {
+                Style::new().light_blue().reversed().bold()
+            } else if selected_opcode == Some(name.as_str()) {
+                Style::new().bold()
+            } else {
+                Style::new()
+            };
+            let bar = (count * bar_width).div_ceil(max_count);
+            Line::from_iter([
+                Span::styled(format!("{name:name_width$} "), style),
+                Span::styled("█".repeat(bar), Style::new().light_cyan()),
+                Span::styled(format!(" {count}"), Style::new().dim()),
+            ])
+        })
+        .collect();
+
+    let title = Span::styled(
+        if state.histogram_reachable {
+            "Opcodes (reachable blocks)"
+        } else {
+            "Opcodes"
+        },
+        Style::new().bold(),
+    );
+
+    let total_y = lines.len() as i64;
+    let inner_area = area.inner(Margin {
+        horizontal: 0,
+        vertical: 1,
+    });
+    let target = state.histogram_cursor as i64;
+    let (scroll, middle) = centered_scroll(total_y, target, target + 1, inner_area.height);
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .scroll((scroll, 0))
+            .block(pane_block(title, focused)),
+        area,
+    );
+
+    frame.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+        inner_area,
+        &mut ScrollbarState::new(total_y as usize).position(middle.max(0) as usize),
+    );
+}
+
+fn inspector_ui(frame: &mut Frame, state: &mut State) {
+    // Place the dialog in the center
+    let v_layout = Layout::new(
+        Direction::Vertical,
+        [
+            Constraint::Fill(1),
+            Constraint::Max(20),
             Constraint::Fill(1),
         ],
     )
@@ -559,7 +2270,120 @@ fn inspector_ui(frame: &mut Frame, state: &mut State) {
             block_layout[0],
         );
 
-        frame.render_widget(Paragraph::new(debug_instruction.as_str()), block_layout[1]);
+        let mut text = Text::default();
+        if let Some(call_line) = Pseudocode::new(&block.view_ir).call_line(index) {
+            text.push_line(Line::from_iter([
+                Span::styled("call: ", Style::new().bold()),
+                Span::styled(call_line, Style::new().light_cyan()),
+            ]));
+        }
+        if let Some(value) = &block.constants[index] {
+            let note = if !analysis::is_foldable(instruction) {
+                ""
+            } else if block.loops.iter().any(|l| l.body.contains(&index)) {
+                " (could be computed at compile time, and is in a loop)"
+            } else {
+                " (could be computed at compile time)"
+            };
+            text.push_line(Line::from_iter([
+                Span::styled("constant: ", Style::new().bold()),
+                Span::styled(analysis::display_constant(value), Style::new().light_blue()),
+                Span::styled(note, Style::new().dim()),
+            ]));
+        }
+        if let Some(profile) = block.profile.get(index) {
+            text.push_line(Line::from_iter([
+                Span::styled("profile: ", Style::new().bold()),
+                if profile.hits == 0 {
+                    Span::styled("never ran", Style::new().dim())
+                } else {
+                    Span::styled(
+                        format!(
+                            "ran {} times, {} in total, {} per run",
+                            profile.hits,
+                            format_duration(profile.duration_ms),
+                            format_duration(profile.duration_ms / profile.hits as f64)
+                        ),
+                        Style::new().bg(heat_color(profile.duration_ms / max_duration(block))),
+                    )
+                },
+            ]));
+        }
+        if let Some(note) = branch_coverage_note(block, index) {
+            text.push_line(Line::from_iter([
+                Span::styled("coverage: ", Style::new().bold()),
+                Span::styled(note, Style::new().light_red()),
+            ]));
+        }
+        if let Some(types) = &block.types.entry[index] {
+            // The types of the registers that are read, and of the one that's written
+            let mut spans = vec![Span::styled("types: ", Style::new().bold())];
+            for (position, reg_id) in registers::uses(instruction).into_iter().enumerate() {
+                let reg_types = types.register(reg_id);
+                if position > 0 {
+                    spans.push(Span::raw(", "));
+                }
+                spans.push(Span::styled(
+                    format!("{reg_id}: {reg_types}"),
+                    types_style(&reg_types),
+                ));
+            }
+            if let Some((reg_id, output)) = &block.types.output[index] {
+                spans.push(Span::raw(" ⇒ "));
+                spans.push(Span::styled(
+                    format!("{reg_id}: {output}"),
+                    types_style(output),
+                ));
+            }
+            if spans.len() > 1 {
+                text.push_line(Line::from(spans));
+            }
+        }
+        if state.show_ssa {
+            // Link each SSA value back to the instruction that produced it
+            let ssa = Ssa::new(
+                &block.view_ir.ir_block.instructions,
+                block.view_ir.ir_block.register_count,
+            );
+            for reg_id in registers::uses(instruction) {
+                let Some(value) = ssa.value(index, reg_id) else {
+                    continue;
+                };
+                let origin = match value {
+                    SsaValue::Entry => "from the start of the block".into(),
+                    SsaValue::Phi(at) => format!("from the φ at {at}"),
+                    SsaValue::Def(at) => format!("written at {at}"),
+                };
+                text.push_line(Line::from_iter([
+                    Span::styled("ssa: ", Style::new().bold()),
+                    Span::styled(
+                        format!("{} {origin}", ssa.name(reg_id, value)),
+                        Style::new().light_magenta(),
+                    ),
+                ]));
+            }
+        }
+        if let Some(reason) = &block.unreachable[index] {
+            text.push_line(Line::styled(
+                format!("unreachable: {reason}"),
+                Style::new().dim(),
+            ));
+        }
+        for lint in block.lints.iter().filter(|lint| lint.index == Some(index)) {
+            text.push_line(Line::styled(
+                format!("{}: {}", lint.severity, lint.message),
+                Style::new().light_yellow(),
+            ));
+        }
+        if !text.lines.is_empty() {
+            text.push_line("");
+        }
+        text.extend(Text::raw(debug_instruction.as_str()));
+
+        frame.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: false }),
+            block_layout[1],
+        );
 
         frame.render_widget(
             Paragraph::new(Line::from_iter([
@@ -571,3 +2395,450 @@ fn inspector_ui(frame: &mut Frame, state: &mut State) {
         );
     }
 }
+
+/// Add the lines for a provenance tree, drawing the branches with box characters
+fn push_provenance_lines(
+    lines: &mut Vec<Line<'static>>,
+    block: &BlockState,
+    node: &Provenance,
+    prefix: &str,
+    last: bool,
+    root: bool,
+) {
+    let formatted = |index: usize| block.view_ir.formatted_instructions[index].as_str();
+    let (label, style) = match node.source {
+        Source::BlockInput => ("the input to the block".to_string(), Style::new().italic()),
+        Source::Empty => (
+            format!("{} is empty, nothing was written to it", node.reg_id),
+            Style::new().light_red(),
+        ),
+        Source::Phi(index) => (
+            format!("{index:4}: φ, depending on where control came from"),
+            Style::new().light_magenta(),
+        ),
+        Source::Instruction(index) => (format!("{index:4}: {}", formatted(index)), Style::new()),
+        Source::Store(index) => (
+            format!("{index:4}: {}", formatted(index)),
+            Style::new().light_green(),
+        ),
+        Source::Argument(index) => (
+            format!("{index:4}: {}", formatted(index)),
+            Style::new().light_cyan(),
+        ),
+    };
+    let branch = match (root, last) {
+        (true, _) => "",
+        (false, false) => "├─ ",
+        (false, true) => "└─ ",
+    };
+    let mut line = Line::from_iter([
+        Span::styled(format!("{prefix}{branch}"), Style::new().dim()),
+        Span::styled(label, style),
+    ]);
+    if node.repeated {
+        line.push_span(Span::styled(" (see above)", Style::new().dim().italic()));
+    }
+    lines.push(line);
+
+    let child_prefix = match (root, last) {
+        (true, _) => prefix.to_string(),
+        (false, false) => format!("{prefix}│  "),
+        (false, true) => format!("{prefix}   "),
+    };
+    for (index, child) in node.children.iter().enumerate() {
+        let last = index + 1 == node.children.len();
+        push_provenance_lines(lines, block, child, &child_prefix, last, false);
+    }
+}
+
+fn provenance_ui(frame: &mut Frame, state: &mut State) {
+    // Place the dialog in the center
+    let v_layout = Layout::new(
+        Direction::Vertical,
+        [
+            Constraint::Fill(1),
+            Constraint::Percentage(80),
+            Constraint::Fill(1),
+        ],
+    )
+    .split(frame.size());
+    let h_layout = Layout::new(
+        Direction::Horizontal,
+        [
+            Constraint::Fill(1),
+            Constraint::Max(100),
+            Constraint::Fill(1),
+        ],
+    )
+    .split(v_layout[1]);
+    let dialog_size = h_layout[1];
+
+    let block = Block::bordered().title(Span::styled("Value provenance", Style::new().bold()));
+    let block_inner = block.inner(dialog_size);
+    frame.render_widget(Clear, dialog_size);
+    frame.render_widget(block, dialog_size);
+
+    let block_layout = Layout::new(
+        Direction::Vertical,
+        [Constraint::Max(2), Constraint::Fill(1), Constraint::Max(2)],
+    )
+    .split(block_inner);
+
+    let Some(index) = state.list_state().selected() else {
+        return;
+    };
+    let block = state.current_block();
+    let ir_block = &block.view_ir.ir_block;
+    let ssa = Ssa::new(&ir_block.instructions, ir_block.register_count);
+
+    frame.render_widget(
+        Paragraph::new(Line::from_iter([
+            Span::styled(format!("{index:4}: "), Style::new().dim()),
+            Span::raw(block.view_ir.formatted_instructions[index].as_str()),
+        ]))
+        .block(Block::new().borders(Borders::BOTTOM)),
+        block_layout[0],
+    );
+
+    // A tree for each register the instruction reads
+    let mut lines = vec![];
+    for tree in analysis::provenance(&ir_block.instructions, &ssa, index) {
+        if !lines.is_empty() {
+            lines.push(Line::raw(""));
+        }
+        lines.push(Line::styled(
+            format!("{} comes from:", tree.reg_id),
+            Style::new().bold(),
+        ));
+        push_provenance_lines(&mut lines, block, &tree, "", true, true);
+    }
+    if lines.is_empty() {
+        lines.push(Line::styled(
+            "(the instruction doesn't read any registers)",
+            Style::new().dim().italic(),
+        ));
+    }
+
+    let max_scroll = lines.len().saturating_sub(block_layout[1].height as usize);
+    state.provenance_scroll = state.provenance_scroll.min(max_scroll as u16);
+    frame.render_widget(
+        Paragraph::new(lines).scroll((state.provenance_scroll, 0)),
+        block_layout[1],
+    );
+
+    frame.render_widget(
+        Paragraph::new(Line::from_iter([
+            Span::styled("<esc>", Style::new().light_blue().bold()),
+            Span::styled(" close  ", Style::new().italic()),
+            Span::styled("<↑/↓/k/j>", Style::new().light_blue().bold()),
+            Span::styled(" scroll", Style::new().italic()),
+        ]))
+        .block(Block::new().borders(Borders::TOP)),
+        block_layout[2],
+    );
+}
+
+fn emulator_ui(frame: &mut Frame, state: &mut State, area: Rect) {
+    let block = state.current_block();
+    let Some(emulator) = &block.emulator else {
+        return;
+    };
+    let value_style = Style::new().light_cyan();
+    let heading = |text: &'static str| Line::styled(text, Style::new().bold());
+
+    let mut lines = vec![match &emulator.status {
+        Status::Running => Line::styled(
+            format!("next: {} ({} steps)", emulator.pc, emulator.steps),
+            Style::new().light_green(),
+        ),
+        Status::Returned(value) => Line::styled(
+            format!("returned {}", analysis::display_constant(value)),
+            Style::new().light_green().bold(),
+        ),
+        Status::Failed(error) => Line::styled(format!("error: {error}"), Style::new().light_red()),
+        Status::Unsupported(reason) => Line::styled(
+            format!("stopped at {}: {reason}", emulator.pc),
+            Style::new().light_yellow(),
+        ),
+    }];
+
+    lines.push(Line::raw(""));
+    lines.push(heading("Registers"));
+    for (reg, value) in emulator.registers.iter().enumerate() {
+        lines.push(match value {
+            Some(value) => Line::from_iter([
+                Span::raw(format!("%{reg} = ")),
+                Span::styled(analysis::display_constant(value), value_style),
+            ]),
+            None => Line::styled(format!("%{reg} empty"), Style::new().dim()),
+        });
+    }
+
+    if !emulator.variables.is_empty() {
+        lines.push(Line::raw(""));
+        lines.push(heading("Variables"));
+        for (var_id, value) in &emulator.variables {
+            lines.push(Line::from_iter([
+                Span::raw(format!("{} = ", variable_name(block, *var_id))),
+                Span::styled(analysis::display_constant(value), value_style),
+            ]));
+        }
+    }
+
+    if !emulator.env.is_empty() {
+        lines.push(Line::raw(""));
+        lines.push(heading("Environment"));
+        for (name, value) in &emulator.env {
+            lines.push(Line::from_iter([
+                Span::raw(format!("$env.{name} = ")),
+                Span::styled(analysis::display_constant(value), value_style),
+            ]));
+        }
+    }
+
+    if !emulator.arguments.is_empty() {
+        lines.push(Line::raw(""));
+        lines.push(heading("Arguments"));
+        for argument in &emulator.arguments {
+            lines.push(match argument {
+                Argument::Positional(value) => {
+                    Line::styled(analysis::display_constant(value), value_style)
+                }
+                Argument::Spread(value) => Line::styled(
+                    format!("...{}", analysis::display_constant(value)),
+                    value_style,
+                ),
+                Argument::Flag(name) => Line::raw(format!("--{name}")),
+                Argument::Named(name, value) => Line::from_iter([
+                    Span::raw(format!("--{name} ")),
+                    Span::styled(analysis::display_constant(value), value_style),
+                ]),
+            });
+        }
+    }
+
+    if !emulator.error_handlers.is_empty() {
+        lines.push(Line::raw(""));
+        lines.push(heading("Error handlers"));
+        for (index, error_register) in emulator.error_handlers.iter().rev() {
+            lines.push(Line::raw(match error_register {
+                Some(reg_id) => format!("catch at {index} into {reg_id}"),
+                None => format!("catch at {index}"),
+            }));
+        }
+    }
+
+    frame.render_widget(
+        Paragraph::new(lines).block(pane_block(
+            Span::styled("Emulator", Style::new().bold()),
+            false,
+        )),
+        area,
+    );
+}
+
+fn env_ui(frame: &mut Frame, state: &mut State) {
+    // Place the dialog in the center
+    let v_layout = Layout::new(
+        Direction::Vertical,
+        [
+            Constraint::Fill(1),
+            Constraint::Percentage(60),
+            Constraint::Fill(1),
+        ],
+    )
+    .split(frame.size());
+    let h_layout = Layout::new(
+        Direction::Horizontal,
+        [
+            Constraint::Fill(1),
+            Constraint::Max(70),
+            Constraint::Fill(1),
+        ],
+    )
+    .split(v_layout[1]);
+    let dialog_size = h_layout[1];
+
+    let block = Block::bordered().title(Span::styled("Environment variables", Style::new().bold()));
+    let block_inner = block.inner(dialog_size);
+    frame.render_widget(Clear, dialog_size);
+    frame.render_widget(block, dialog_size);
+
+    let block_layout = Layout::new(
+        Direction::Vertical,
+        [Constraint::Fill(1), Constraint::Max(2)],
+    )
+    .split(block_inner);
+
+    let accesses = analysis::env_accesses(&state.current_block().view_ir.ir_block);
+    let usage = analysis::env_usage(&accesses);
+    state.env_cursor = state.env_cursor.min(usage.len().saturating_sub(1));
+
+    if usage.is_empty() {
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                "(the block doesn't use the environment)",
+                Style::new().dim().italic(),
+            )),
+            block_layout[0],
+        );
+    } else {
+        let width = usage.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let items = usage.iter().map(|(name, uses)| {
+            let count = |kinds: &[EnvAccessKind]| {
+                uses.iter()
+                    .filter(|access| kinds.contains(&access.kind))
+                    .count()
+            };
+            let reads = count(&[
+                EnvAccessKind::Read,
+                EnvAccessKind::ReadOptional,
+                EnvAccessKind::ReadAll,
+            ]);
+            let optional = count(&[EnvAccessKind::ReadOptional]);
+            let writes = count(&[EnvAccessKind::Write]);
+            let mut line = Line::from_iter([
+                Span::styled(format!("{name:width$}"), Style::new().light_cyan()),
+                Span::raw(format!("  {reads} reads, {writes} writes")),
+            ]);
+            if optional > 0 {
+                line.push_span(Span::styled(
+                    format!(" ({optional} optional)"),
+                    Style::new().dim(),
+                ));
+            }
+            ListItem::new(line)
+        });
+        frame.render_stateful_widget(
+            List::new(items).highlight_style(Style::new().reversed()),
+            block_layout[0],
+            &mut ListState::default().with_selected(Some(state.env_cursor)),
+        );
+    }
+
+    frame.render_widget(
+        Paragraph::new(Line::from_iter([
+            Span::styled("<esc>", Style::new().light_blue().bold()),
+            Span::styled(" close  ", Style::new().italic()),
+            Span::styled("<↑/↓/k/j>", Style::new().light_blue().bold()),
+            Span::styled(" select  ", Style::new().italic()),
+            Span::styled("<enter>", Style::new().light_blue().bold()),
+            Span::styled(" show only its uses", Style::new().italic()),
+        ]))
+        .block(Block::new().borders(Borders::TOP)),
+        block_layout[1],
+    );
+}
+
+/// Every key binding for the help dialog, in the same order as the README
+const KEY_BINDINGS: &[(&str, &str)] = &[
+    ("q", "Quit the application."),
+    (
+        "space",
+        "Open the inspector, which shows debug info for the instruction.",
+    ),
+    ("g", "Go to a specific instruction by index."),
+    ("/", "Show only the instructions that match a filter."),
+    ("↑/k", "Go to the previous instruction."),
+    ("↓/j", "Go to the next instruction."),
+    ("[", "Go back after jumping to an instruction's target."),
+    (
+        "]",
+        "Jump into an instruction's target (branch or call decl / block).",
+    ),
+    (
+        "c",
+        "Go to the error handler (`catch`) covering the instruction.",
+    ),
+    ("e", "Open the source of the instruction in `$env.EDITOR`."),
+    ("a", "Toggle a pane showing the parsed AST of the block."),
+    (
+        "z",
+        "Fold the basic block around the instruction, or unfold it.",
+    ),
+    ("Z", "Fold the body of the loop around the instruction."),
+    ("f", "Fold or unfold the argument setup of every call."),
+    (
+        "p",
+        "Toggle a pane showing pseudocode decompiled from the IR.",
+    ),
+    (
+        "o",
+        "Toggle an outline of the loops in the block, and focus it.",
+    ),
+    ("s", "Toggle SSA form in the instruction list."),
+    (
+        "v",
+        "Trace where the values read by the instruction came from.",
+    ),
+    (
+        "t",
+        "Toggle the inferred type of each register that's written.",
+    ),
+    (
+        "H",
+        "Toggle a histogram of the opcodes in the block, and focus it.",
+    ),
+    (
+        "E",
+        "Filter the instructions by the environment variable they use.",
+    ),
+    (
+        "x",
+        "Start emulating the block from the beginning, or stop.",
+    ),
+    ("n", "Run the next instruction in the emulator."),
+    ("N", "Run the emulator until the block returns or stops."),
+    (
+        "tab",
+        "Switch focus between the instruction list and the source code.",
+    ),
+    ("esc", "Close a dialog box or prompt, or remove the filter."),
+    ("?", "Show or hide this list."),
+];
+
+fn help_ui(frame: &mut Frame) {
+    // Place the dialog in the center, tall enough for every key
+    let height = u16::try_from(KEY_BINDINGS.len() + 2).unwrap_or(u16::MAX);
+    let v_layout = Layout::new(
+        Direction::Vertical,
+        [
+            Constraint::Fill(1),
+            Constraint::Length(height),
+            Constraint::Fill(1),
+        ],
+    )
+    .split(frame.size());
+    let h_layout = Layout::new(
+        Direction::Horizontal,
+        [
+            Constraint::Fill(1),
+            Constraint::Max(80),
+            Constraint::Fill(1),
+        ],
+    )
+    .split(v_layout[1]);
+    let dialog_size = h_layout[1];
+
+    let width = KEY_BINDINGS
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Line> = KEY_BINDINGS
+        .iter()
+        .map(|(key, effect)| {
+            Line::from_iter([
+                Span::styled(format!(" {key:width$}  "), Style::new().light_blue().bold()),
+                Span::raw(*effect),
+            ])
+        })
+        .collect();
+
+    frame.render_widget(Clear, dialog_size);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::bordered().title(Span::styled("Key bindings", Style::new().bold()))),
+        dialog_size,
+    );
+}